@@ -0,0 +1,40 @@
+/// Grammars in `vendor/` that are compiled with the `syntax` feature.
+#[cfg(feature = "syntax")]
+const GRAMMARS: [&str; 6] = ["c", "go", "java", "javascript", "python", "rust"];
+
+/// Compile the generated parser of a grammar (and its external scanner, if it has one).
+#[cfg(feature = "syntax")]
+fn compile_grammar(name: &str) {
+    let src_dir = std::path::Path::new("vendor")
+        .join(format!("tree-sitter-{name}"))
+        .join("src");
+
+    let mut build = cc::Build::new();
+    build
+        .std("c11")
+        .include(&src_dir)
+        // The parsers are generated, so their warnings aren't ours to fix
+        .warnings(false);
+
+    #[cfg(target_env = "msvc")]
+    build.flag("-utf-8");
+
+    for file in ["parser.c", "scanner.c"] {
+        let path = src_dir.join(file);
+        if path.exists() {
+            println!("cargo:rerun-if-changed={}", path.display());
+            build.file(path);
+        }
+    }
+
+    build.compile(&format!("tree-sitter-{name}"));
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "syntax")]
+    for name in GRAMMARS {
+        compile_grammar(name);
+    }
+}
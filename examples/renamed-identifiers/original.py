@@ -0,0 +1,7 @@
+def total_price(items, tax_rate):
+    subtotal = 0
+    for item in items:
+        if item.quantity > 0:
+            subtotal += item.price * item.quantity
+    tax = subtotal * tax_rate
+    return subtotal + tax
@@ -0,0 +1,7 @@
+def order_cost(products, vat):
+    running = 0
+    for product in products:
+        if product.count > 1:
+            running += product.cost * product.count
+    levy = running * vat
+    return running + levy
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::types::{JsonMatch, JsonRoot};
+
+/// The fingerprints of matches that are already known about (see `--baseline`).
+///
+/// Fingerprints only depend on the contents of the blocks, so matches stay known when the code
+/// around them moves.
+#[derive(Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+pub struct Baseline {
+    pub version: String,
+    pub fingerprints: BTreeSet<String>,
+}
+
+impl From<&JsonRoot> for Baseline {
+    fn from(root: &JsonRoot) -> Self {
+        Self {
+            version: root.version.clone(),
+            fingerprints: root
+                .matches
+                .iter()
+                .map(|jm| jm.fingerprint.clone())
+                .filter(|fingerprint| !fingerprint.is_empty())
+                .collect(),
+        }
+    }
+}
+
+impl Baseline {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let contents = serde_json::to_string_pretty(&self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, contents + "\n")
+    }
+
+    pub fn contains(&self, jm: &JsonMatch) -> bool {
+        self.fingerprints.contains(&jm.fingerprint)
+    }
+
+    /// Remove the matches that are already known about, and count them in `known_matches`.
+    pub fn filter(&self, root: &mut JsonRoot) {
+        let count = root.unique_matches();
+        root.retain_matches(|jm| !self.contains(jm));
+        root.known_matches += count - root.unique_matches();
+    }
+}
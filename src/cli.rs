@@ -1,8 +1,20 @@
-use clap::{Parser, ValueEnum};
-use std::io;
-use std::path::PathBuf;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Default)]
+use crate::config::{self, Config, Origin, Settings};
+use crate::encoding::Encoding;
+use crate::lang::Language;
+use crate::normalize::Normalization;
+use crate::strip::Strip;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Default, Debug)]
 pub enum ReportingMode {
     /// Plain text
     #[default]
@@ -11,6 +23,68 @@ pub enum ReportingMode {
     ///
     /// Can be piped into `jq` for querying or loaded into `vim` with the plugin.
     Json,
+    /// As JSON Lines, streamed while searching
+    ///
+    /// Every pair of matching blocks is printed as a `{"type": "pair"}` line as soon as it is
+    /// found. The last line is `{"type": "result"}`, with the same fields as `json`.
+    Jsonl,
+    /// As a SARIF 2.1.0 log, for code scanning dashboards
+    ///
+    /// Has one result per duplicated block, which points at the other blocks of its group.
+    Sarif,
+    /// As XML in the format of PMD's Copy/Paste Detector (CPD)
+    CpdXml,
+    /// As a GitLab Code Quality report (the Code Climate issue format)
+    ///
+    /// Has one issue per duplicated block, with a severity that grows with the size of the block
+    /// and the number of copies.
+    Gitlab,
+    /// As a single HTML page that works offline
+    ///
+    /// Shows the blocks of every match side by side, with the characters that differ highlighted.
+    Html,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Default, Debug)]
+pub enum Engine {
+    /// Compare every pair of files line by line
+    #[default]
+    Pairwise,
+    /// Find repeated blocks across all files at once with a suffix array
+    ///
+    /// Only the lines that start a repeated block are compared, in the pairs of files that share
+    /// one, so it finds the same matches as `pairwise` but is much faster on large numbers of
+    /// files. A block that is in every file (like a license header) still pairs up every file,
+    /// so the worst case is still quadratic. Only supports strict equality (`-t 0`) without gaps
+    /// (`--max-gap 0`). Falls back to `pairwise` otherwise.
+    SuffixArray,
+    /// Find functions, methods, classes and blocks that are syntactically equal
+    ///
+    /// Files are parsed with tree-sitter, so blocks always line up with the code, and formatting
+    /// and comments don't matter. Use `--normalize` to also find units that only differ in their
+    /// identifiers or literals. Supports C, Go, Java, JavaScript, Python and Rust, and ignores
+    /// files in other languages.
+    ///
+    /// Only available if superdiff was built with the `syntax` feature.
+    Syntax,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Default, Debug)]
+pub enum ColorChoice {
+    /// Only when printing to a terminal (and `NO_COLOR` isn't set)
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Default, Debug)]
+pub enum SortOrder {
+    /// By the path and line of the first block of each match, then of the blocks after it
+    #[default]
+    Location,
+    /// By the number of blocks in each match, largest first
+    Size,
 }
 
 #[derive(Parser, Clone)]
@@ -35,6 +109,18 @@ pub struct Cli {
     #[arg(short, long, default_value_t = 10)]
     pub block_threshold: usize,
 
+    /// Number of non-matching lines that a block can skip over
+    ///
+    /// Lets a block continue across lines that were inserted, deleted or changed in one of the
+    /// copies, as long as the ratio of matching lines stays at or above `--min-similarity`. The
+    /// default of 0 only allows blocks of consecutive matching lines.
+    #[arg(long, default_value_t = 0)]
+    pub max_gap: usize,
+
+    /// Minimum ratio of matching lines to block length when skipping over lines
+    #[arg(long, default_value_t = 0.8, value_parser = parse_ratio)]
+    pub min_similarity: f64,
+
     /// Set to increase the details that are output
     ///
     /// Output preamble on the command line options that are specified, as well as a progress bar.
@@ -52,18 +138,419 @@ pub struct Cli {
     #[arg(long, default_value_t = 1)]
     pub worker_threads: usize,
 
+    /// Replace tokens with placeholders before comparing lines
+    ///
+    /// Useful for finding blocks that were copied and then had their variables renamed or their
+    /// constants changed. Reported lines still refer to the original text.
+    #[arg(value_enum, long, value_delimiter = ',')]
+    pub normalize: Vec<Normalization>,
+
+    /// Leave comments or blank lines out of comparisons
+    ///
+    /// Blocks can then span lines that were left out, so that e.g. a comment in one copy doesn't
+    /// split it. Reported lines and sizes still refer to the lines of the file. Comments are
+    /// found by the language of the file (see `--lang`), and are not stripped from files in other
+    /// languages.
+    #[arg(value_enum, long, value_delimiter = ',')]
+    pub strip: Vec<Strip>,
+
+    /// Algorithm used to find matching blocks
+    #[arg(value_enum, long, default_value_t = Engine::Pairwise)]
+    pub engine: Engine,
+
     /// Files to find the code blocks (leave empty to read from stdin)
+    ///
+    /// Directories are searched recursively, skipping hidden files and anything matched by
+    /// `.gitignore` or `.ignore` files.
     pub files: Vec<PathBuf>,
 
+    /// Don't respect `.gitignore` files when searching directories
+    #[arg(long, default_value_t = false)]
+    pub no_ignore_vcs: bool,
+
+    /// Don't respect `.ignore` files when searching directories
+    #[arg(long, default_value_t = false)]
+    pub no_ignore_dot: bool,
+
+    /// Search hidden files and directories
+    #[arg(long, default_value_t = false)]
+    pub hidden: bool,
+
+    /// Only search files that match the glob (can be given multiple times)
+    ///
+    /// Globs are matched against the whole path, and against every part of it below the searched
+    /// directory or the directory of the configuration file, so `*.rs` selects every Rust file.
+    #[arg(long, value_parser = parse_glob)]
+    pub include: Vec<String>,
+
+    /// Don't search files that match the glob (can be given multiple times)
+    ///
+    /// For example, `target/**` skips everything in every `target` directory.
+    #[arg(long, value_parser = parse_glob)]
+    pub exclude: Vec<String>,
+
+    /// Only search files written in these languages
+    ///
+    /// Languages are detected by file extension, or by shebang for files without a known
+    /// extension.
+    #[arg(value_enum, long, value_delimiter = ',')]
+    pub lang: Vec<Language>,
+
+    /// Encoding of the files
+    ///
+    /// Line endings can be either `\n` or `\r\n` regardless.
+    #[arg(value_enum, long, default_value_t = Encoding::Auto)]
+    pub encoding: Encoding,
+
+    /// Exit with status 4 if any file could not be read
+    ///
+    /// Binary files are skipped regardless, and are not counted as errors.
+    #[arg(long, default_value_t = false)]
+    pub strict: bool,
+
+    /// Trim every block so that it starts and ends on a syntactic boundary
+    ///
+    /// Blocks never start on a closing bracket or in the middle of a statement, and never end
+    /// halfway through an `if` or a function. Uses balanced brackets for languages like C, and
+    /// indentation for languages like Python. Every block of a match is trimmed the same way, and
+    /// matches that end up shorter than `-b` are left out. Matches with gap lines (see
+    /// `--max-gap`) are left as they are. The `pair` lines of `--reporting-mode jsonl` are not
+    /// trimmed.
+    #[arg(long, default_value_t = false)]
+    pub snap: bool,
+
+    /// Print the lines of every block in text mode
+    ///
+    /// With `-t` or `--normalize`, every other block of a match is shown as an inline diff against
+    /// the first one.
+    #[arg(long, default_value_t = false)]
+    pub snippets: bool,
+
+    /// Number of lines to print before and after every block (with `--snippets`)
+    #[arg(short = 'C', long, default_value_t = 0, requires = "snippets")]
+    pub context: usize,
+
+    /// When to color the snippets
+    #[arg(value_enum, long, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// Leave out matches that are in this baseline file
+    ///
+    /// Matches are identified by the contents of their blocks, so they stay in the baseline when
+    /// they move around. The `pair` lines of `--reporting-mode jsonl` are not filtered.
+    #[arg(long, value_name = "FILE")]
+    pub baseline: Option<PathBuf>,
+
+    /// Write every match that was found to this baseline file (see `--baseline`)
+    #[arg(long, value_name = "FILE")]
+    pub write_baseline: Option<PathBuf>,
+
+    /// Exit with status 3 if more than this ratio of all lines is duplicated
+    ///
+    /// The ratio is between 0 and 1 (see `--summary`).
+    #[arg(long, value_parser = parse_ratio)]
+    pub fail_on_duplication_ratio: Option<f64>,
+
+    /// Exit with status 3 if there are more than this many unique matches
+    #[arg(long)]
+    pub fail_on_groups: Option<usize>,
+
+    /// Exit with status 3 if any block is longer than this many lines
+    #[arg(long)]
+    pub fail_on_block_size: Option<usize>,
+
+    /// Print a table of how much of each file and directory is duplicated in text mode
+    ///
+    /// The same metrics are always included in other reporting modes that support them.
+    #[arg(long, default_value_t = false)]
+    pub summary: bool,
+
+    /// Order of the matches in the output
+    ///
+    /// Files and blocks within a match are always sorted by path and line.
+    #[arg(value_enum, long, default_value_t = SortOrder::Location)]
+    pub sort: SortOrder,
+
     /// How you want the information to be delivered
     ///
     /// Anything other than `ReportingMode::Text` will disable metadata reporting (e.g. reporting file
     /// information, verbosity, and other command line arguments, as well as the concluding remarks).
     #[arg(value_enum, long, default_value_t = ReportingMode::Text)]
     pub reporting_mode: ReportingMode,
+
+    /// Read settings from this file instead of searching for `.superdiff.toml`
+    ///
+    /// By default, `.superdiff.toml` is searched for in the current directory and then in each of
+    /// its parents. Options given on the command line always take precedence over the file.
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Don't read settings from any configuration file
+    #[arg(long, default_value_t = false, conflicts_with_all = ["config", "profile"])]
+    pub no_config: bool,
+
+    /// Also use the settings in the `[profile.<NAME>]` section of the configuration file
+    ///
+    /// Settings in the profile take precedence over the ones at the top of the file.
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Where the value of every option that can be set in the configuration file came from
+    #[arg(skip)]
+    pub origins: BTreeMap<&'static str, Origin>,
+
+    /// The directories that were searched by `expand_directories`
+    #[arg(skip)]
+    pub searched_dirs: Vec<PathBuf>,
+}
+
+/// Options that can be set in the configuration file (see `config::Settings`).
+const CONFIGURABLE: [&str; 12] = [
+    "lev_threshold",
+    "line_threshold",
+    "block_threshold",
+    "max_gap",
+    "min_similarity",
+    "worker_threads",
+    "include",
+    "exclude",
+    "reporting_mode",
+    "fail_on_duplication_ratio",
+    "fail_on_groups",
+    "fail_on_block_size",
+];
+
+/// Sets options from the configuration file, unless they were given on the command line.
+struct Merge<'a> {
+    matches: &'a ArgMatches,
+    origin: &'a Origin,
+    origins: &'a mut BTreeMap<&'static str, Origin>,
+}
+
+impl Merge<'_> {
+    fn set<T>(&mut self, id: &'static str, option: &mut T, value: Option<T>) {
+        let Some(value) = value else {
+            return;
+        };
+
+        if self.matches.value_source(id) != Some(ValueSource::CommandLine) {
+            *option = value;
+            self.origins.insert(id, self.origin.clone());
+        }
+    }
+}
+
+fn check_ratio(ratio: f64) -> Result<f64, String> {
+    if (0.0..=1.0).contains(&ratio) {
+        Ok(ratio)
+    } else {
+        Err("must be between 0 and 1".to_owned())
+    }
+}
+
+fn parse_ratio(ratio: &str) -> Result<f64, String> {
+    check_ratio(ratio.parse::<f64>().map_err(|e| e.to_string())?)
+}
+
+fn parse_glob(glob: &str) -> Result<String, String> {
+    Glob::new(glob)
+        .map(|_| glob.to_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// Build a set out of globs that were already checked by `parse_glob`.
+fn build_glob_set(globs: &[String]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).unwrap());
+    }
+
+    builder.build().unwrap()
+}
+
+/// Whether the path (without any leading `./`) matches any of the globs, or any tail of it below
+/// one of the (absolute) `bases` or its own directory.
+///
+/// This anchors globs like gitignore does, so `target/**` matches every `target` directory below
+/// a base, however deep it is and however the path was given, and the file name always counts.
+fn matches_glob_set(set: &GlobSet, path: &Path, bases: &[PathBuf]) -> bool {
+    let given = path.strip_prefix(".").unwrap_or(path);
+    if set.is_match(given) {
+        return true;
+    }
+
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_owned());
+    bases
+        .iter()
+        .map(PathBuf::as_path)
+        .chain(path.parent())
+        .filter_map(|base| path.strip_prefix(base).ok())
+        .any(|relative| {
+            let mut tail = relative.components();
+            while !tail.as_path().as_os_str().is_empty() {
+                if set.is_match(tail.as_path()) {
+                    return true;
+                }
+                tail.next();
+            }
+            false
+        })
+}
+
+impl Default for Cli {
+    /// The options you would get if you ran `superdiff` without any arguments.
+    fn default() -> Self {
+        Self::parse_from(["superdiff"])
+    }
 }
 
 impl Cli {
+    /// Parse the command line, and take every option that wasn't given from the configuration
+    /// file.
+    ///
+    /// The configuration file is `--config`, or else the closest `.superdiff.toml` to `dir`. Once
+    /// loaded, `config` is set to the file that was used.
+    ///
+    /// Fails instead of exiting on invalid arguments or `--help`, so call `exit` on the error to
+    /// print it the way clap does.
+    pub fn load_from<I, T>(itr: I, dir: &Path) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Self::command().try_get_matches_from(itr)?;
+        let mut cli = Self::from_arg_matches(&matches)?;
+        for id in CONFIGURABLE {
+            if matches.value_source(id) == Some(ValueSource::CommandLine) {
+                cli.origins.insert(id, Origin::CommandLine);
+            }
+        }
+
+        cli.load_config(&matches, dir)
+            .map_err(|e| Self::command().error(ErrorKind::InvalidValue, e))?;
+        if cli.engine == Engine::Syntax && !cfg!(feature = "syntax") {
+            return Err(Self::command().error(
+                ErrorKind::InvalidValue,
+                "--engine syntax is unavailable: superdiff was built without the `syntax` feature",
+            ));
+        }
+        Ok(cli)
+    }
+
+    /// Take every option that wasn't given on the command line from the configuration file.
+    fn load_config(&mut self, matches: &ArgMatches, dir: &Path) -> Result<(), String> {
+        let path = match (&self.config, self.no_config) {
+            (Some(path), _) => Some(path.clone()),
+            (None, true) => None,
+            (None, false) => config::find(dir),
+        };
+        let Some(config) = path.map(|path| Config::read(&path)).transpose()? else {
+            return match &self.profile {
+                Some(name) => Err(format!(
+                    "--profile {name} needs a configuration file, but no {} was found",
+                    config::FILE_NAME
+                )),
+                None => Ok(()),
+            };
+        };
+
+        let origin = Origin::File {
+            path: config.path.clone(),
+            profile: None,
+        };
+        self.merge(matches, &config.settings, &origin)?;
+        if let Some(name) = self.profile.clone() {
+            let origin = Origin::File {
+                path: config.path.clone(),
+                profile: Some(name.clone()),
+            };
+            self.merge(matches, config.profile(&name)?, &origin)?;
+        }
+
+        self.config = Some(config.path);
+        Ok(())
+    }
+
+    /// Take the options that weren't given on the command line from some settings.
+    fn merge(
+        &mut self,
+        matches: &ArgMatches,
+        settings: &Settings,
+        origin: &Origin,
+    ) -> Result<(), String> {
+        for glob in settings.include.iter().chain(&settings.exclude).flatten() {
+            parse_glob(glob).map_err(|e| format!("{origin}: {e}"))?;
+        }
+        if let Some(ratio) = settings.min_similarity {
+            check_ratio(ratio).map_err(|e| format!("{origin}: min-similarity {e}"))?;
+        }
+        if let Some(ratio) = settings.fail_on_duplication_ratio {
+            check_ratio(ratio).map_err(|e| format!("{origin}: fail-on-duplication-ratio {e}"))?;
+        }
+
+        let settings = settings.clone();
+        let mut merge = Merge {
+            matches,
+            origin,
+            origins: &mut self.origins,
+        };
+        merge.set(
+            "lev_threshold",
+            &mut self.lev_threshold,
+            settings.lev_threshold,
+        );
+        merge.set(
+            "line_threshold",
+            &mut self.line_threshold,
+            settings.line_threshold,
+        );
+        merge.set(
+            "block_threshold",
+            &mut self.block_threshold,
+            settings.block_threshold,
+        );
+        merge.set("max_gap", &mut self.max_gap, settings.max_gap);
+        merge.set(
+            "min_similarity",
+            &mut self.min_similarity,
+            settings.min_similarity,
+        );
+        merge.set(
+            "worker_threads",
+            &mut self.worker_threads,
+            settings.worker_threads,
+        );
+        merge.set("include", &mut self.include, settings.include);
+        merge.set("exclude", &mut self.exclude, settings.exclude);
+        merge.set(
+            "reporting_mode",
+            &mut self.reporting_mode,
+            settings.reporting_mode,
+        );
+        merge.set(
+            "fail_on_duplication_ratio",
+            &mut self.fail_on_duplication_ratio,
+            settings.fail_on_duplication_ratio.map(Some),
+        );
+        merge.set(
+            "fail_on_groups",
+            &mut self.fail_on_groups,
+            settings.fail_on_groups.map(Some),
+        );
+        merge.set(
+            "fail_on_block_size",
+            &mut self.fail_on_block_size,
+            settings.fail_on_block_size.map(Some),
+        );
+
+        Ok(())
+    }
+
+    /// Where the value of an option came from, e.g. `command line`.
+    pub fn origin(&self, id: &str) -> &Origin {
+        self.origins.get(id).unwrap_or(&Origin::Default)
+    }
+
     pub fn populate_files_from_stdin(&mut self) {
         let mut files: Vec<PathBuf> = Vec::new();
 
@@ -77,10 +564,94 @@ impl Cli {
         self.files = files;
     }
 
+    /// Replace every directory in `files` with the files inside of it.
+    ///
+    /// Files inside a directory are sorted by name. Files that were given explicitly are kept,
+    /// even if they would have been ignored. The directories are kept in `searched_dirs`, so that
+    /// `filter_files` can match globs below them.
+    pub fn expand_directories(&mut self) {
+        let mut files: Vec<PathBuf> = Vec::new();
+
+        for path in &self.files {
+            if !path.is_dir() {
+                files.push(path.clone());
+                continue;
+            }
+
+            self.searched_dirs.push(path.clone());
+            let walk = WalkBuilder::new(path)
+                .hidden(!self.hidden)
+                .git_ignore(!self.no_ignore_vcs)
+                // Not the global ignore file, so that results don't depend on who runs them
+                .git_global(false)
+                .git_exclude(!self.no_ignore_vcs)
+                .require_git(false)
+                .ignore(!self.no_ignore_dot)
+                .sort_by_file_name(|a, b| a.cmp(b))
+                .build();
+
+            for entry in walk {
+                match entry {
+                    Ok(entry) if entry.file_type().is_some_and(|t| t.is_file()) => {
+                        files.push(entry.into_path());
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("{e}"),
+                }
+            }
+        }
+
+        self.files = files;
+    }
+
+    /// Remove files from `files` that aren't selected by `--include`, `--exclude` and `--lang`.
+    pub fn filter_files(&mut self) {
+        let include = build_glob_set(&self.include);
+        let exclude = build_glob_set(&self.exclude);
+        let config_dir = self.config.as_deref().and_then(Path::parent);
+        let bases: Vec<PathBuf> = self
+            .searched_dirs
+            .iter()
+            .map(PathBuf::as_path)
+            .chain(config_dir)
+            .filter_map(|dir| std::path::absolute(dir).ok())
+            .collect();
+
+        self.files.retain(|file| {
+            (self.include.is_empty() || matches_glob_set(&include, file, &bases))
+                && !matches_glob_set(&exclude, file, &bases)
+                && (self.lang.is_empty()
+                    || Language::from_path(file).is_some_and(|lang| self.lang.contains(&lang)))
+        });
+    }
+
     pub fn files_from_stdin(&self) -> bool {
         self.files.is_empty()
     }
 
+    pub fn uses_syntax(&self) -> bool {
+        self.engine == Engine::Syntax
+    }
+
+    pub fn uses_suffix_array(&self) -> bool {
+        self.engine == Engine::SuffixArray && self.lev_threshold == 0 && self.max_gap == 0
+    }
+
+    /// Whether lines can match without being equal, so it is worth showing how they differ.
+    pub fn allows_differences(&self) -> bool {
+        self.lev_threshold > 0 || !self.normalize.is_empty()
+    }
+
+    pub fn uses_color(&self) -> bool {
+        match self.color {
+            ColorChoice::Auto => {
+                io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+
     pub fn print(&self) {
         if !self.verbose {
             return;
@@ -93,24 +664,101 @@ impl Cli {
             eprintln!(" {:?}...", &self.files[..10]);
         }
 
-        eprintln!("Worker threads: {}", self.worker_threads);
+        if let Some(config) = &self.config {
+            eprint!("Configuration file (--config): {}", config.display());
+            match &self.profile {
+                Some(profile) => eprintln!(", profile (--profile): {profile}"),
+                None => eprintln!(),
+            }
+        }
+        if !self.include.is_empty() || !self.exclude.is_empty() || !self.lang.is_empty() {
+            eprintln!(
+                "Include (--include): {:?} (from {}), exclude (--exclude): {:?} (from {}), languages (--lang): {:?}",
+                self.include,
+                self.origin("include"),
+                self.exclude,
+                self.origin("exclude"),
+                self.lang
+            );
+        }
+        eprintln!("Encoding (--encoding): {:?}", self.encoding);
+        eprintln!(
+            "Worker threads (--worker-threads): {} (from {})",
+            self.worker_threads,
+            self.origin("worker_threads")
+        );
+        eprintln!(
+            "Engine (--engine): {}",
+            if self.uses_syntax() {
+                "Syntax"
+            } else if self.uses_suffix_array() {
+                "Suffix array"
+            } else {
+                "Pairwise"
+            }
+        );
         eprintln!("Verbosity (-v): {}", self.verbose);
+        if self.snippets {
+            eprintln!("Snippets (--snippets): context of {} line(s)", self.context);
+        }
         eprintln!(
-            "Comparison threshold (-t): {} ({})",
+            "Comparison threshold (-t): {} ({}) (from {})",
             self.lev_threshold,
             if self.lev_threshold > 0 {
                 "Levenshtein distance"
             } else {
                 "Strict equality"
-            }
+            },
+            self.origin("lev_threshold")
+        );
+        eprintln!("Normalized tokens (--normalize): {:?}", self.normalize);
+        if self.snap {
+            eprintln!("Snap to syntactic boundaries (--snap): {}", self.snap);
+        }
+        if !self.strip.is_empty() {
+            eprintln!("Stripped lines (--strip): {:?}", self.strip);
+        }
+        eprintln!(
+            "Minimum length of first line before block consideration (-l): {} (from {})",
+            self.line_threshold,
+            self.origin("line_threshold")
         );
         eprintln!(
-            "Minimum length of first line before block consideration (-l): {}",
-            self.line_threshold
+            "Minimum length of block before consideration (-b): {} (from {})",
+            self.block_threshold,
+            self.origin("block_threshold")
         );
+        if self.max_gap > 0 {
+            eprintln!(
+                "Maximum gap lines in a block (--max-gap): {} (from {}) (minimum similarity {} from {})",
+                self.max_gap,
+                self.origin("max_gap"),
+                self.min_similarity,
+                self.origin("min_similarity")
+            );
+        }
         eprintln!(
-            "Minimum length of block before consideration (-b): {}",
-            self.block_threshold
+            "Reporting mode (--reporting-mode): {} (from {})",
+            self.reporting_mode.to_possible_value().unwrap().get_name(),
+            self.origin("reporting_mode")
         );
+        if let Some(ratio) = self.fail_on_duplication_ratio {
+            eprintln!(
+                "Maximum duplication ratio (--fail-on-duplication-ratio): {ratio} (from {})",
+                self.origin("fail_on_duplication_ratio")
+            );
+        }
+        if let Some(groups) = self.fail_on_groups {
+            eprintln!(
+                "Maximum unique matches (--fail-on-groups): {groups} (from {})",
+                self.origin("fail_on_groups")
+            );
+        }
+        if let Some(size) = self.fail_on_block_size {
+            eprintln!(
+                "Maximum block size (--fail-on-block-size): {size} (from {})",
+                self.origin("fail_on_block_size")
+            );
+        }
     }
 }
@@ -9,13 +9,19 @@ const SUBSTITUTION_COST: usize = 1;
 
 /// Create a comparison function based on the given threshold.
 ///
-/// If the threshold is 0, we use string comparison. If not, we use Levenshtein distance.
+/// If the threshold is 0, we compare the interned line ids. If not, we use Levenshtein distance
+/// (which is only computed when the lines aren't equal). Suppressed lines never match.
 pub fn comparison_lambda(args: &Cli) -> ComparisonFn {
     let threshold = args.lev_threshold;
     if threshold == 0 {
-        Box::new(move |x, y| x == y)
+        Box::new(move |x, y| x.id == y.id && !x.suppressed && !y.suppressed)
     } else {
-        Box::new(move |x, y| levenshtein_distance(x, y, threshold) <= threshold)
+        Box::new(move |x, y| {
+            !x.suppressed
+                && !y.suppressed
+                && (x.id == y.id
+                    || levenshtein_distance(x.comparable(), y.comparable(), threshold) <= threshold)
+        })
     }
 }
 
@@ -47,6 +53,110 @@ fn get_max_block_size(comp: &ComparisonFn, f1: &CompFile, f2: &CompFile) -> usiz
     }
 }
 
+/// Whether line `i1` of the first file and line `i2` of the second file are in bounds and match.
+///
+/// Also fails if a block starting at `f1.start` would reach into a block starting at `f2.start`
+/// in the same file.
+fn lines_match(
+    comp: &ComparisonFn,
+    f1: &CompFile,
+    f2: &CompFile,
+    (i1, i2): (usize, usize),
+) -> bool {
+    if f1.file == f2.file && i1 >= f2.start {
+        return false;
+    }
+
+    i1 < f1.lines.len() && i2 < f2.lines.len() && comp(&f1.lines[i1], &f2.lines[i2])
+}
+
+/// Whether a gap can skip over the `count` lines of the file from line `start`.
+///
+/// Suppressed lines can't be skipped, so that they never extend a block.
+fn can_skip(f: &CompFile, start: usize, count: usize) -> bool {
+    f.lines
+        .get(start..start + count)
+        .is_some_and(|lines| lines.iter().all(|line| !line.suppressed))
+}
+
+/// Find the sizes of a matching code block that can skip over non-matching lines.
+///
+/// When the next lines don't match, we look for the closest pair of matching lines that is at
+/// most `max_gap` lines away (in total) and continue from there. Skipping `a` lines in the first
+/// file and `b` lines in the second file counts as max(a, b) gap lines, since lines that are
+/// skipped in both files are just changed lines. Suppressed lines are never skipped over, so the
+/// block stops at them.
+///
+/// The block always ends on a matching line, and the ratio of matching lines to the size of the
+/// block in the larger file must stay at or above `min_similarity`.
+///
+/// Returns the size of the block in the first file, the size in the second file, and the number
+/// of lines that were skipped in each file.
+fn get_max_gapped_block_size(
+    args: &Cli,
+    comp: &ComparisonFn,
+    f1: &CompFile,
+    f2: &CompFile,
+) -> (usize, usize, (usize, usize)) {
+    let (mut size1, mut size2, mut gap_lines) = (1, 1, 0);
+    let (mut skipped1, mut skipped2) = (0, 0);
+    let mut matched_lines = 1;
+    let mut best = (1, 1, (0, 0));
+
+    loop {
+        let next = (f1.start + size1, f2.start + size2);
+
+        if lines_match(comp, f1, f2, next) {
+            size1 += 1;
+            size2 += 1;
+            matched_lines += 1;
+
+            let similarity = matched_lines as f64 / std::cmp::max(size1, size2) as f64;
+            if similarity >= args.min_similarity {
+                best = (size1, size2, (skipped1, skipped2));
+            }
+            continue;
+        }
+
+        let skip = (1..=args.max_gap.saturating_sub(gap_lines)).find_map(|gap| {
+            std::iter::once((gap, gap))
+                .chain((0..gap).flat_map(|other| [(gap, other), (other, gap)]))
+                .find(|&(a, b)| {
+                    can_skip(f1, next.0, a)
+                        && can_skip(f2, next.1, b)
+                        && lines_match(comp, f1, f2, (next.0 + a, next.1 + b))
+                })
+                .map(|(a, b)| (a, b, gap))
+        });
+
+        match skip {
+            Some((a, b, gap)) => {
+                size1 += a;
+                size2 += b;
+                skipped1 += a;
+                skipped2 += b;
+                gap_lines += gap;
+            }
+            None => return best,
+        }
+    }
+}
+
+/// Find the sizes of the matching code block in both files, and the number of gap lines in each.
+fn get_block_sizes(
+    args: &Cli,
+    comp: &ComparisonFn,
+    f1: &CompFile,
+    f2: &CompFile,
+) -> (usize, usize, (usize, usize)) {
+    if args.max_gap == 0 {
+        let block_length = get_max_block_size(comp, f1, f2);
+        (block_length, block_length, (0, 0))
+    } else {
+        get_max_gapped_block_size(args, comp, f1, f2)
+    }
+}
+
 /// Add or remove entries from lookup and matches based on given pair of matches.
 ///
 /// There are 4 situations that we prepare for.
@@ -100,6 +210,20 @@ pub fn update_matches(
 }
 
 pub fn get_matches_from_2_files(
+    args: &Cli,
+    tx: &mpsc::Sender<(Match, Match)>,
+    comp: &ComparisonFn,
+    (f1, f2): (CompFile, CompFile),
+) {
+    if args.lev_threshold == 0 {
+        get_matches_from_2_files_indexed(args, tx, comp, (f1, f2));
+    } else {
+        get_matches_from_2_files_scanning(args, tx, comp, (f1, f2));
+    }
+}
+
+/// Compare every line of the first file against every line of the second file.
+fn get_matches_from_2_files_scanning(
     args: &Cli,
     tx: &mpsc::Sender<(Match, Match)>,
     comp: &ComparisonFn,
@@ -109,7 +233,7 @@ pub fn get_matches_from_2_files(
 
     while f1.start < f1.lines.len() {
         // Don't consider line lengths below the threshold
-        if f1.current_line().len() < args.line_threshold {
+        if f1.current_line().text.len() < args.line_threshold {
             f1.start += 1;
             continue;
         }
@@ -119,18 +243,18 @@ pub fn get_matches_from_2_files(
 
         while f2.start < f2.lines.len() {
             if comp(f1.current_line(), f2.current_line()) {
-                let block_length = get_max_block_size(comp, &f1, &f2);
+                let (size1, size2, gap_lines) = get_block_sizes(args, comp, &f1, &f2);
 
-                if block_length < args.block_threshold {
-                    f2.start += block_length;
+                if std::cmp::min(size1, size2) < args.block_threshold {
+                    f2.start += size2;
                     continue;
                 }
 
-                let matches = Match::from_compfiles(&f1, &f2, block_length);
+                let matches = Match::from_compfiles(&f1, &f2, (size1, size2), gap_lines);
                 tx.send(matches).unwrap_or(());
 
-                f2.start += block_length;
-                max_block_length = std::cmp::max(max_block_length, block_length);
+                f2.start += size2;
+                max_block_length = std::cmp::max(max_block_length, size1);
             } else {
                 f2.start += 1;
             }
@@ -140,6 +264,78 @@ pub fn get_matches_from_2_files(
     }
 }
 
+/// Compare lines of the first file against only the equal lines of the second file.
+///
+/// Equivalent to `get_matches_from_2_files_scanning` with strict equality: lines that don't match
+/// only ever advance the cursor in the second file by 1, so jumping straight to the next equal
+/// line that hasn't been eaten by a previous block visits the same block starts.
+fn get_matches_from_2_files_indexed(
+    args: &Cli,
+    tx: &mpsc::Sender<(Match, Match)>,
+    comp: &ComparisonFn,
+    (f1, f2): (CompFile, CompFile),
+) {
+    let starts: Vec<usize> = (0..f1.lines.len()).collect();
+    get_matches_from_lines(args, tx, comp, (f1, f2), &starts);
+}
+
+/// Compare the given lines of the first file against only the equal lines of the second file.
+///
+/// `starts` must be in increasing order. Lines that aren't in it are skipped, so this is
+/// equivalent to `get_matches_from_2_files_indexed` as long as none of them can start a match:
+/// those lines only ever advance the first file by 1 without finding anything.
+pub fn get_matches_from_lines(
+    args: &Cli,
+    tx: &mpsc::Sender<(Match, Match)>,
+    comp: &ComparisonFn,
+    (mut f1, mut f2): (CompFile, CompFile),
+    starts: &[usize],
+) {
+    // The first line that isn't part of a block that was already found
+    let mut next_start = 0;
+
+    for &start in starts {
+        if start < next_start {
+            continue;
+        }
+        f1.start = start;
+
+        // Don't consider line lengths below the threshold, or suppressed lines
+        if f1.current_line().text.len() < args.line_threshold || f1.current_line().suppressed {
+            continue;
+        }
+
+        let Some(candidates) = f2.index.get(&f1.current_line().id) else {
+            continue;
+        };
+
+        let mut cursor = if f1.file == f2.file { f1.start + 1 } else { 0 };
+        let mut max_block_length = 1;
+        let first = candidates.partition_point(|&i| i < cursor);
+
+        for &candidate in &candidates[first..] {
+            if candidate < cursor {
+                continue;
+            }
+
+            f2.start = candidate;
+            let (size1, size2, gap_lines) = get_block_sizes(args, comp, &f1, &f2);
+            cursor = candidate + size2;
+
+            if std::cmp::min(size1, size2) < args.block_threshold {
+                continue;
+            }
+
+            let matches = Match::from_compfiles(&f1, &f2, (size1, size2), gap_lines);
+            tx.send(matches).unwrap_or(());
+
+            max_block_length = std::cmp::max(max_block_length, size1);
+        }
+
+        next_start = start + max_block_length;
+    }
+}
+
 /// Make a `Vec<char>`.
 ///
 /// We use a preallocated `Vec` instead of `.collect()` to avoid allocation penalties.
@@ -153,81 +349,191 @@ fn to_char_vec(s: &str) -> Vec<char> {
     v
 }
 
-/// Compute the edit distance of 2 strings, with shortcuts.
-///
-/// Modified from wikipedia pseudocode for matrix approach (no recursion).
-///
-/// For strings x and y with length m and n respectively, we create an m+1 by n+1 matrix
-/// (represented by 1d array) of costs where moving to the right constitutes as inserting a
-/// character from y; moving down constitutes as deleting a character from y; moving diagonally
-/// across constitutes as substituting a character from y into a.
-///
-/// We stop computing if we find that nothing of our current row is under the threshold, in which
-/// case we would exit early.
-///
-/// We can also stop computing if we know that the threshold is greater than m + n, which is the
-/// maximum.
+/// Bitmasks of the positions at which each character appears in a pattern.
 ///
-/// This algorithm runs at a time complexity of O(mn).
-#[allow(clippy::needless_range_loop)]
-pub fn levenshtein_distance(x: &str, y: &str, threshold: usize) -> usize {
-    let (x, y) = (to_char_vec(x), to_char_vec(y));
-    let (m, n) = (x.len(), y.len());
-    let mut d = vec![0usize; (m + 1) * (n + 1)];
-    let size = m + 1;
+/// ASCII characters are looked up in a table; anything else falls back to a (usually empty) list.
+struct PatternMasks {
+    ascii: [u64; 128],
+    other: Vec<(char, u64)>,
+}
 
-    // Distance is at most the length of the longer string
-    if threshold >= std::cmp::max(m, n) {
-        return threshold;
-    }
+impl PatternMasks {
+    fn new(pattern: &str) -> Self {
+        let mut masks = Self {
+            ascii: [0; 128],
+            other: Vec::new(),
+        };
+
+        for (i, c) in pattern.chars().enumerate() {
+            let bit = 1u64 << i;
+            if c.is_ascii() {
+                masks.ascii[c as usize] |= bit;
+            } else {
+                match masks.other.iter_mut().find(|(other, _)| *other == c) {
+                    Some((_, mask)) => *mask |= bit,
+                    None => masks.other.push((c, bit)),
+                }
+            }
+        }
 
-    // Distance is at least the absolute value of the difference in sizes of the two strings
-    if threshold < m.abs_diff(n) {
-        return threshold + 1;
+        masks
     }
 
-    for i in 1..(m + 1) {
-        d[i] = i;
+    fn get(&self, c: char) -> u64 {
+        if c.is_ascii() {
+            self.ascii[c as usize]
+        } else {
+            self.other
+                .iter()
+                .find(|(other, _)| *other == c)
+                .map_or(0, |&(_, mask)| mask)
+        }
     }
+}
 
-    for j in 1..(n + 1) {
-        d[j * size] = j;
+/// Compute the edit distance with Myers' bit-parallel algorithm (as formulated by Hyyrö).
+///
+/// Each column of the DP matrix is encoded as bit vectors of the positive and negative vertical
+/// differences between neighbouring cells, so a whole column is computed in a handful of word
+/// operations. The pattern must be between 1 and 64 characters long (`m`).
+///
+/// We stop computing if the distance can't come back under the threshold with the characters that
+/// are left in the text, in which case we return `threshold + 1`.
+///
+/// This algorithm runs at a time complexity of O(n).
+fn myers_distance(pattern: &str, m: usize, text: &str, n: usize, threshold: usize) -> usize {
+    let masks = PatternMasks::new(pattern);
+    let last = 1u64 << (m - 1);
+    let mut pv = !0u64;
+    let mut mv = 0u64;
+    let mut score = m;
+
+    for (j, c) in text.chars().enumerate() {
+        let eq = masks.get(c);
+        let xv = eq | mv;
+        let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+        let mut ph = mv | !(xh | pv);
+        let mut mh = pv & xh;
+
+        if ph & last != 0 {
+            score += 1;
+        } else if mh & last != 0 {
+            score -= 1;
+        }
+
+        // Every remaining character can bring the score down by at most 1
+        if score > threshold + (n - j - 1) {
+            return threshold + 1;
+        }
+
+        ph = (ph << 1) | 1;
+        mh <<= 1;
+        pv = mh | !(xv | ph);
+        mv = ph & xv;
     }
 
-    for j in 1..(n + 1) {
-        let mut has_changed_row = false;
+    score
+}
+
+/// Compute the edit distance, only looking at cells that are close to the diagonal.
+///
+/// Any path through the DP matrix that strays more than `threshold` cells away from the diagonal
+/// costs more than `threshold`, so we only compute a band of 2 * `threshold` + 1 cells per row,
+/// keeping 2 rows at a time. Cells outside the band are treated as `threshold + 1`.
+///
+/// We stop computing if we find that nothing of our current row is under the threshold, in which
+/// case we would exit early.
+///
+/// This algorithm runs at a time complexity of O(kn), where k is the threshold.
+fn banded_distance(x: &str, y: &str, threshold: usize) -> usize {
+    let (x, y) = (to_char_vec(x), to_char_vec(y));
+    let (m, n) = (x.len(), y.len());
+    let outside = threshold + 1;
+    let mut prev: Vec<usize> = (0..=n).map(|j| std::cmp::min(j, outside)).collect();
+    let mut curr = vec![outside; n + 1];
+
+    for i in 1..=m {
+        let lo = std::cmp::max(1, i.saturating_sub(threshold));
+        let hi = std::cmp::min(n, i + threshold);
+        curr[lo - 1] = if lo == 1 {
+            std::cmp::min(i, outside)
+        } else {
+            outside
+        };
+        let mut row_min = curr[lo - 1];
 
-        for i in 1..(m + 1) {
+        for j in lo..=hi {
             let sub_cost = if x[i - 1] == y[j - 1] {
                 0
             } else {
                 SUBSTITUTION_COST
             };
-            d[i + j * size] = std::cmp::min(
-                d[(i - 1) + j * size] + INSERTION_COST,
-                std::cmp::min(
-                    d[i + (j - 1) * size] + DELETION_COST,
-                    d[(i - 1) + (j - 1) * size] + sub_cost,
-                ),
-            );
-
-            if d[i + j * size] <= threshold {
-                has_changed_row = true;
-            }
+            curr[j] = (prev[j - 1] + sub_cost)
+                .min(curr[j - 1] + INSERTION_COST)
+                .min(prev[j] + DELETION_COST)
+                .min(outside);
+            row_min = std::cmp::min(row_min, curr[j]);
         }
 
         // Guarantee to not pass the threshold check
-        if !has_changed_row {
-            return threshold + 1;
+        if row_min > threshold {
+            return outside;
         }
+
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[n]
+}
+
+/// Compute the edit distance of 2 strings, with shortcuts.
+///
+/// Returns the distance if it is at most the threshold, and `threshold + 1` otherwise.
+///
+/// We can stop computing if we know that the threshold is at least the length of the longer
+/// string, which is the maximum, in which case we return the threshold itself. We can also stop if
+/// the difference in length is greater than the threshold, since that is the minimum.
+///
+/// Otherwise, we use Myers' bit-parallel algorithm if the shorter string fits in a machine word,
+/// and a banded version of the matrix approach if not.
+pub fn levenshtein_distance(x: &str, y: &str, threshold: usize) -> usize {
+    let (m, n) = (x.chars().count(), y.chars().count());
+
+    // Distance is at most the length of the longer string
+    if threshold >= std::cmp::max(m, n) {
+        return threshold;
+    }
+
+    // Distance is at least the absolute value of the difference in sizes of the two strings
+    if threshold < m.abs_diff(n) {
+        return threshold + 1;
     }
 
-    d[m + n * size]
+    // The pattern should be the shorter string
+    let ((x, m), (y, n)) = if m <= n {
+        ((x, m), (y, n))
+    } else {
+        ((y, n), (x, m))
+    };
+
+    let distance = if m == 0 {
+        n
+    } else if m <= u64::BITS as usize {
+        myers_distance(x, m, y, n, threshold)
+    } else {
+        banded_distance(x, y, threshold)
+    };
+
+    std::cmp::min(distance, threshold + 1)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::levenshtein_distance;
+    use super::*;
+    use crate::cli::ReportingMode;
+    use crate::types::Line;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
 
     macro_rules! check_lev {
         ( $a:literal, $b:literal, $t:literal ) => {{
@@ -244,6 +550,113 @@ mod tests {
         }};
     }
 
+    #[test]
+    fn test_gapped_blocks() {
+        let args = Cli {
+            block_threshold: 4,
+            max_gap: 2,
+            min_similarity: 0.75,
+            ..Cli::default()
+        };
+        let mut ids = HashMap::new();
+        let a = compfile(
+            &mut ids,
+            "a",
+            &["a", "b", "c", "d", "e", "f", "g", "h", "z"],
+        );
+        let b = compfile(
+            &mut ids,
+            "b",
+            &["a", "b", "c", "log", "d", "e", "X", "g", "h", "y", "y"],
+        );
+
+        let pairs = collect_pairs(
+            get_matches_from_2_files_scanning,
+            &args,
+            (a.clone(), b.clone()),
+        );
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].0.line, pairs[0].0.size), (1, 8));
+        assert_eq!((pairs[0].1.line, pairs[0].1.size), (1, 9));
+        assert_eq!((pairs[0].0.gap_lines, pairs[0].1.gap_lines), (1, 2));
+
+        // Not enough gap lines allowed to get past both differences
+        let args = Cli { max_gap: 1, ..args };
+        let pairs = collect_pairs(
+            get_matches_from_2_files_scanning,
+            &args,
+            (a.clone(), b.clone()),
+        );
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].0.size, pairs[0].1.size), (5, 6));
+
+        // Too many gaps for the block to be similar enough
+        let args = Cli {
+            max_gap: 2,
+            min_similarity: 0.95,
+            ..args
+        };
+        let pairs = collect_pairs(get_matches_from_2_files_scanning, &args, (a, b));
+        assert!(pairs.is_empty());
+    }
+
+    /// The full matrix approach, for reference.
+    fn reference_distance(x: &str, y: &str) -> usize {
+        let (x, y) = (to_char_vec(x), to_char_vec(y));
+        let mut prev: Vec<usize> = (0..=y.len()).collect();
+
+        for i in 1..=x.len() {
+            let mut curr = vec![i; y.len() + 1];
+            for j in 1..=y.len() {
+                let sub_cost = usize::from(x[i - 1] != y[j - 1]);
+                curr[j] = (prev[j - 1] + sub_cost)
+                    .min(curr[j - 1] + 1)
+                    .min(prev[j] + 1);
+            }
+            prev = curr;
+        }
+
+        prev[y.len()]
+    }
+
+    #[test]
+    fn test_lev_distance_matches_reference() {
+        let short = [
+            "",
+            "a",
+            "kitten",
+            "sitting",
+            "flaw",
+            "lawn",
+            "ünïcödé",
+            "unicode",
+        ];
+        let long = [
+            "let x = some_function(argument_one, argument_two, argument_three, argument_four);",
+            "let y = some_function(argument_one, argument_2, argument_three, argument_four, 5);",
+            "let x = some_function(argument_one, argument_two, argument_three, argument_four)",
+            "ẞ let x = some_function(argument_one, argument_two, argumënt_three, argument_four);",
+        ];
+
+        for strings in [&short[..], &long[..]] {
+            for x in strings {
+                for y in strings {
+                    let expected = reference_distance(x, y);
+                    for t in 0..12 {
+                        let dist = levenshtein_distance(x, y, t);
+                        if t >= std::cmp::max(x.chars().count(), y.chars().count()) {
+                            assert_eq!(dist, t);
+                        } else if expected <= t {
+                            assert_eq!(dist, expected, "{x:?} {y:?} {t}");
+                        } else {
+                            assert_eq!(dist, t + 1, "{x:?} {y:?} {t}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_lev_distance() {
         // Normal use of function
@@ -260,4 +673,75 @@ mod tests {
         // A bit tight
         check_lev!("the same", "the same", 0);
     }
+
+    type GetMatchesFn =
+        fn(&Cli, &mpsc::Sender<(Match, Match)>, &ComparisonFn, (CompFile, CompFile));
+
+    fn collect_pairs(
+        get_matches: GetMatchesFn,
+        args: &Cli,
+        files: (CompFile, CompFile),
+    ) -> Vec<(Match, Match)> {
+        let (tx, rx) = mpsc::channel();
+        let comp = comparison_lambda(args);
+        get_matches(args, &tx, &comp, files);
+        drop(tx);
+
+        rx.into_iter().collect()
+    }
+
+    /// Make a file, interning its lines through `ids` so that equal lines in any file share an id.
+    fn compfile<'a>(ids: &mut HashMap<&'a str, usize>, file: &str, lines: &[&'a str]) -> CompFile {
+        let lines = lines
+            .iter()
+            .enumerate()
+            .map(|(i, &l)| Line {
+                id: {
+                    let next_id = ids.len();
+                    *ids.entry(l).or_insert(next_id)
+                },
+                text: l.to_owned(),
+                raw: l.to_owned(),
+                normalized: None,
+                suppressed: false,
+                number: i + 1,
+            })
+            .collect();
+
+        CompFile::new(PathBuf::from(file), lines)
+    }
+
+    #[test]
+    fn test_indexed_matches_scanning() {
+        let args = Cli {
+            lev_threshold: 0,
+            line_threshold: 1,
+            block_threshold: 2,
+            verbose: false,
+            files: vec![],
+            reporting_mode: ReportingMode::Json,
+            worker_threads: 1,
+            ..Cli::default()
+        };
+        let mut ids = HashMap::new();
+        let a = compfile(
+            &mut ids,
+            "a",
+            &[
+                "x", "y", "z", "", "x", "y", "z", "x", "y", "w", "x", "x", "x", "x",
+            ],
+        );
+        let b = compfile(
+            &mut ids,
+            "b",
+            &["q", "x", "y", "z", "x", "y", "", "x", "x", "x"],
+        );
+
+        for files in [(a.clone(), a.clone()), (a.clone(), b.clone()), (b, a)] {
+            assert_eq!(
+                collect_pairs(get_matches_from_2_files_scanning, &args, files.clone()),
+                collect_pairs(get_matches_from_2_files_indexed, &args, files),
+            );
+        }
+    }
 }
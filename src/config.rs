@@ -0,0 +1,209 @@
+use clap::ValueEnum;
+use serde::{de::Error, Deserialize, Deserializer};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::ReportingMode;
+
+/// Name of the configuration file that is searched for (see `find`).
+pub const FILE_NAME: &str = ".superdiff.toml";
+
+/// Settings that can be given in a configuration file.
+///
+/// Keys have the same names as the command line flags, e.g. `block-threshold = 5`.
+#[derive(Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    pub lev_threshold: Option<usize>,
+    pub line_threshold: Option<usize>,
+    pub block_threshold: Option<usize>,
+    pub max_gap: Option<usize>,
+    pub min_similarity: Option<f64>,
+    pub worker_threads: Option<usize>,
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "value_enum")]
+    pub reporting_mode: Option<ReportingMode>,
+    pub fail_on_duplication_ratio: Option<f64>,
+    pub fail_on_groups: Option<usize>,
+    pub fail_on_block_size: Option<usize>,
+}
+
+/// Parse a value by the same names that are accepted on the command line.
+fn value_enum<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: ValueEnum,
+{
+    let Some(name) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+
+    T::from_str(&name, false).map(Some).map_err(|_| {
+        let names: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(|v| Some(v.to_possible_value()?.get_name().to_owned()))
+            .collect();
+        D::Error::custom(format!(
+            "invalid value `{name}`, expected one of: {}",
+            names.join(", ")
+        ))
+    })
+}
+
+/// The contents of a configuration file.
+///
+/// Settings at the top of the file always apply. Settings in a `[profile.<name>]` section only
+/// apply with `--profile <name>`, and take precedence over the ones at the top.
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct Config {
+    pub path: PathBuf,
+    pub settings: Settings,
+    pub profiles: BTreeMap<String, Settings>,
+}
+
+/// Where the effective value of an option came from (shown with `-v`).
+#[derive(Default, Clone, PartialEq, Eq, Debug)]
+pub enum Origin {
+    #[default]
+    Default,
+    CommandLine,
+    File {
+        path: PathBuf,
+        profile: Option<String>,
+    },
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::CommandLine => write!(f, "command line"),
+            Self::File {
+                path,
+                profile: None,
+            } => write!(f, "{}", path.display()),
+            Self::File {
+                path,
+                profile: Some(profile),
+            } => write!(f, "{} [profile.{profile}]", path.display()),
+        }
+    }
+}
+
+/// Find the configuration file in `dir` or the closest of its parents.
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+impl Config {
+    pub fn parse(path: &Path, contents: &str) -> Result<Self, String> {
+        let error = |e: &dyn fmt::Display| format!("{}: {e}", path.display());
+
+        let mut table: toml::Table = contents.parse().map_err(|e| error(&e))?;
+        let profiles = match table.remove("profile") {
+            Some(profiles) => profiles.try_into().map_err(|e| error(&e))?,
+            None => BTreeMap::new(),
+        };
+        let settings = table.try_into().map_err(|e| error(&e))?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            settings,
+            profiles,
+        })
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::parse(path, &contents)
+    }
+
+    /// The settings of a profile, or an error that lists the profiles that do exist.
+    pub fn profile(&self, name: &str) -> Result<&Settings, String> {
+        self.profiles.get(name).ok_or_else(|| {
+            format!(
+                "{}: no profile named `{name}` (found: {})",
+                self.path.display(),
+                self.profiles
+                    .keys()
+                    .map(|name| name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find, Config, Settings, FILE_NAME};
+    use crate::cli::ReportingMode;
+
+    use std::fs::{create_dir_all, write};
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            Path::new(FILE_NAME),
+            r#"
+            block-threshold = 5
+            exclude = ["target/**"]
+
+            [profile.ci]
+            reporting-mode = "cpd-xml"
+            fail-on-groups = 0
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.settings,
+            Settings {
+                block_threshold: Some(5),
+                exclude: Some(vec!["target/**".to_owned()]),
+                ..Settings::default()
+            }
+        );
+        assert_eq!(
+            config.profile("ci").unwrap(),
+            &Settings {
+                reporting_mode: Some(ReportingMode::CpdXml),
+                fail_on_groups: Some(0),
+                ..Settings::default()
+            }
+        );
+        assert!(config.profile("local").unwrap_err().contains("found: ci"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let path = Path::new(FILE_NAME);
+
+        assert!(Config::parse(path, "block-treshold = 5")
+            .unwrap_err()
+            .contains("unknown field `block-treshold`"));
+        assert!(Config::parse(path, "reporting-mode = \"xml\"")
+            .unwrap_err()
+            .contains("expected one of: text, json"));
+        assert!(Config::parse(path, "[profile.ci]\nworker-threads = \"4\"").is_err());
+    }
+
+    #[test]
+    fn test_find() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        let nested = dir.join("a/b");
+        create_dir_all(&nested).unwrap();
+        write(dir.join(FILE_NAME), "").unwrap();
+
+        assert_eq!(find(&nested), Some(dir.join(FILE_NAME)));
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt::Write;
+
+use crate::normalize::tokenize;
+use crate::types::{FileCache, JsonRoot};
+
+/// Escape text for use inside an XML attribute.
+fn escape_attribute(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Wrap text in a CDATA section, splitting up any `]]>` that would end it early.
+fn cdata(s: &str) -> String {
+    format!("<![CDATA[{}]]>", s.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+/// Generate a report in the XML format of PMD's Copy/Paste Detector.
+///
+/// Each match becomes a `<duplication>`, with a `<file>` for every block. The code fragment is
+/// taken from the first block, which also decides the `lines` and `tokens` of the duplication.
+/// Files that could not be read are listed as `<error>`s.
+pub fn report(root: &JsonRoot, cache: &FileCache) -> String {
+    let files = cache.by_path();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<pmd-cpd>\n");
+
+    for jm in &root.matches {
+        let blocks = jm.sorted_blocks();
+
+        let (first_path, _, first_info) = blocks[0];
+        let fragment = files
+            .get(first_path)
+            .map(|f| f.block(first_info))
+            .unwrap_or_default();
+        let tokens: usize = fragment.iter().map(|line| tokenize(&line.text).len()).sum();
+
+        writeln!(
+            xml,
+            "  <duplication lines=\"{}\" tokens=\"{}\">",
+            first_info.block_length, tokens
+        )
+        .unwrap();
+        for (path, _, info) in blocks {
+            writeln!(
+                xml,
+                "    <file line=\"{}\" endline=\"{}\" path=\"{}\"/>",
+                info.starting_line,
+                info.starting_line + info.block_length.saturating_sub(1),
+                escape_attribute(&path.to_string_lossy())
+            )
+            .unwrap();
+        }
+        writeln!(
+            xml,
+            "    <codefragment>{}</codefragment>",
+            cdata(
+                &fragment
+                    .iter()
+                    .map(|line| line.raw.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("\n")
+            )
+        )
+        .unwrap();
+        xml.push_str("  </duplication>\n");
+    }
+
+    for e in &root.errors {
+        writeln!(
+            xml,
+            "  <error filename=\"{}\" msg=\"{}\"/>",
+            escape_attribute(&e.path.to_string_lossy()),
+            escape_attribute(&e.error)
+        )
+        .unwrap();
+    }
+
+    xml.push_str("</pmd-cpd>");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cdata, escape_attribute};
+
+    #[test]
+    fn test_escape_attribute() {
+        assert_eq!(
+            escape_attribute(r#"a<b & "c">'d'"#),
+            "a&lt;b &amp; &quot;c&quot;&gt;&apos;d&apos;"
+        );
+    }
+
+    #[test]
+    fn test_cdata() {
+        assert_eq!(
+            cdata("if a[b[0]]>1"),
+            "<![CDATA[if a[b[0]]]]><![CDATA[>1]]>"
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use std::iter::Peekable;
+
+/// Whether a piece of text is in both strings, or only in one of them.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Change {
+    Same,
+    /// Only in the first string.
+    Removed,
+    /// Only in the second string.
+    Added,
+}
+
+/// Largest number of cells in the table of common subsequences before we give up on finding the
+/// differences (this is only meant for single lines).
+const MAX_DIFF_CELLS: usize = 1_000_000;
+
+fn push_segment(segments: &mut Vec<(Change, String)>, change: Change, c: char) {
+    match segments.last_mut() {
+        Some((last, s)) if *last == change => s.push(c),
+        _ => segments.push((change, c.to_string())),
+    }
+}
+
+/// Find the characters that differ between two strings, using their longest common subsequence.
+///
+/// Consecutive characters with the same change are joined into one segment. Dropping the `Added`
+/// segments gives back `a`, and dropping the `Removed` segments gives back `b`. If the strings are
+/// too long to compare, all of `a` is removed and all of `b` is added.
+pub fn diff(a: &str, b: &str) -> Vec<(Change, String)> {
+    let prefix = a
+        .chars()
+        .zip(b.chars())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len_utf8())
+        .sum::<usize>();
+    let suffix = a[prefix..]
+        .chars()
+        .rev()
+        .zip(b[prefix..].chars().rev())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len_utf8())
+        .sum::<usize>();
+
+    let a_middle: Vec<char> = a[prefix..a.len() - suffix].chars().collect();
+    let b_middle: Vec<char> = b[prefix..b.len() - suffix].chars().collect();
+    let (n, m) = (a_middle.len(), b_middle.len());
+
+    let mut segments = Vec::new();
+    if prefix > 0 {
+        segments.push((Change::Same, a[..prefix].to_owned()));
+    }
+
+    if (n + 1) * (m + 1) > MAX_DIFF_CELLS {
+        segments.push((Change::Removed, a_middle.iter().collect()));
+        segments.push((Change::Added, b_middle.iter().collect()));
+    } else {
+        // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..]
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if a_middle[i] == b_middle[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    std::cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && a_middle[i] == b_middle[j] {
+                push_segment(&mut segments, Change::Same, a_middle[i]);
+                i += 1;
+                j += 1;
+            } else if j == m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                push_segment(&mut segments, Change::Removed, a_middle[i]);
+                i += 1;
+            } else {
+                push_segment(&mut segments, Change::Added, b_middle[j]);
+                j += 1;
+            }
+        }
+    }
+
+    if suffix > 0 {
+        let s = &a[a.len() - suffix..];
+        match segments.last_mut() {
+            Some((Change::Same, last)) => last.push_str(s),
+            _ => segments.push((Change::Same, s.to_owned())),
+        }
+    }
+
+    segments.retain(|(_, s)| !s.is_empty());
+    segments
+}
+
+/// Move the `Removed` characters at the front of `changes` over to `segments`.
+fn push_removed(
+    segments: &mut Vec<(Change, String)>,
+    changes: &mut Peekable<impl Iterator<Item = (Change, char)>>,
+) {
+    while let Some((_, c)) = changes.next_if(|&(change, _)| change == Change::Removed) {
+        push_segment(segments, Change::Removed, c);
+    }
+}
+
+/// Like `diff`, but spelled out with `shown` instead of `b`.
+///
+/// `shown` is how `b` is displayed, and has every character of `b` in order, along with some
+/// more (like a line with its indentation and comments, where `b` is the code that was compared).
+/// Characters that are only in `shown` are `Same`, and removed characters go right after the
+/// character of `b` that they followed. If the strings are too long to find where the characters
+/// of `b` are, all of `shown` is `Same`.
+pub fn diff_shown(a: &str, b: &str, shown: &str) -> Vec<(Change, String)> {
+    // Where the characters of `b` are in `shown`
+    let places = diff(b, shown);
+    if places.iter().any(|(place, _)| *place == Change::Removed) {
+        return vec![(Change::Same, shown.to_owned())];
+    }
+
+    let mut changes = diff(a, b)
+        .into_iter()
+        .flat_map(|(change, s)| s.chars().map(move |c| (change, c)).collect::<Vec<_>>())
+        .peekable();
+    let mut segments = Vec::new();
+    for (place, s) in places {
+        for c in s.chars() {
+            if place == Change::Added {
+                push_segment(&mut segments, Change::Same, c);
+                continue;
+            }
+
+            push_removed(&mut segments, &mut changes);
+            let change = changes.next().map_or(Change::Same, |(change, _)| change);
+            push_segment(&mut segments, change, c);
+            push_removed(&mut segments, &mut changes);
+        }
+    }
+    push_removed(&mut segments, &mut changes);
+
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, diff_shown, Change};
+
+    fn side(segments: &[(Change, String)], skip: Change) -> String {
+        segments
+            .iter()
+            .filter(|(change, _)| *change != skip)
+            .map(|(_, s)| s.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(
+            diff("let x = 12;", "let y = 16;"),
+            vec![
+                (Change::Same, "let ".to_owned()),
+                (Change::Removed, "x".to_owned()),
+                (Change::Added, "y".to_owned()),
+                (Change::Same, " = 1".to_owned()),
+                (Change::Removed, "2".to_owned()),
+                (Change::Added, "6".to_owned()),
+                (Change::Same, ";".to_owned()),
+            ]
+        );
+        assert_eq!(
+            diff("same", "same"),
+            vec![(Change::Same, "same".to_owned())]
+        );
+        assert_eq!(diff("", ""), vec![]);
+        assert_eq!(diff("", "new"), vec![(Change::Added, "new".to_owned())]);
+    }
+
+    #[test]
+    fn test_diff_shown() {
+        // Compared without the indentation and the comment
+        assert_eq!(
+            diff_shown("x = 123;", "x = 12;", "    x = 12; // twelve"),
+            vec![
+                (Change::Same, "    x = 12".to_owned()),
+                (Change::Removed, "3".to_owned()),
+                (Change::Same, "; // twelve".to_owned()),
+            ]
+        );
+        assert_eq!(
+            diff_shown("a = 1", "b = 1", "/* b */ b = 1"),
+            vec![
+                (Change::Same, "/* b */ ".to_owned()),
+                (Change::Removed, "a".to_owned()),
+                (Change::Added, "b".to_owned()),
+                (Change::Same, " = 1".to_owned()),
+            ]
+        );
+        assert_eq!(
+            diff_shown("new", "", "  "),
+            vec![
+                (Change::Same, "  ".to_owned()),
+                (Change::Removed, "new".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_sides() {
+        for (a, b) in [
+            ("kitten", "sitting"),
+            ("café au lait", "cafe olé"),
+            ("aaaa", "aa"),
+            ("abc", "xyz"),
+        ] {
+            let segments = diff(a, b);
+            assert_eq!(side(&segments, Change::Added), a);
+            assert_eq!(side(&segments, Change::Removed), b);
+        }
+    }
+}
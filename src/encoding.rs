@@ -0,0 +1,161 @@
+use clap::ValueEnum;
+
+use std::io;
+
+/// Text encodings that files can be decoded from.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Default, Debug)]
+pub enum Encoding {
+    /// Use the byte order mark if there is one, otherwise UTF-8
+    ///
+    /// Invalid UTF-8 is replaced with U+FFFD instead of failing.
+    #[default]
+    Auto,
+    /// UTF-8, failing on invalid UTF-8
+    Utf8,
+    /// UTF-16, little endian
+    Utf16le,
+    /// UTF-16, big endian
+    Utf16be,
+    /// ISO-8859-1
+    Latin1,
+    /// Windows-1252 (a superset of Latin-1 that is common on Windows)
+    Windows1252,
+}
+
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+const UTF16LE_BOM: &[u8] = &[0xff, 0xfe];
+const UTF16BE_BOM: &[u8] = &[0xfe, 0xff];
+
+/// Number of bytes at the start of a file that are checked for binary contents.
+const BINARY_CHECK_LENGTH: usize = 8000;
+
+/// Characters for bytes 0x80 to 0x9f in Windows-1252, where it differs from Latin-1.
+///
+/// Bytes that are undefined in Windows-1252 map to the same code point, like in Latin-1.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+/// Whether the contents look like they belong to a binary file (i.e. there's a NUL byte near the
+/// start, like what `git` and `grep` check for).
+fn is_binary(contents: &[u8]) -> bool {
+    contents[..std::cmp::min(contents.len(), BINARY_CHECK_LENGTH)].contains(&0)
+}
+
+fn decode_utf16(contents: &[u8], to_u16: fn([u8; 2]) -> u16) -> String {
+    let units = contents
+        .chunks(2)
+        .map(|pair| to_u16([pair[0], *pair.get(1).unwrap_or(&0)]));
+
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+fn decode_windows_1252(contents: &[u8]) -> String {
+    contents
+        .iter()
+        .map(|&b| match b {
+            0x80..=0x9f => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+            _ => b as char,
+        })
+        .collect()
+}
+
+/// Decode the contents of a file, or `None` if it looks like a binary file.
+///
+/// A byte order mark at the start of the file is always removed. With `Encoding::Auto`, it also
+/// decides the encoding; files without one are treated as UTF-8. Files are only checked for binary
+/// contents if they aren't UTF-16.
+pub fn decode(contents: Vec<u8>, encoding: Encoding) -> io::Result<Option<String>> {
+    let encoding = match encoding {
+        Encoding::Auto if contents.starts_with(UTF16LE_BOM) => Encoding::Utf16le,
+        Encoding::Auto if contents.starts_with(UTF16BE_BOM) => Encoding::Utf16be,
+        encoding => encoding,
+    };
+
+    let is_utf16 = matches!(encoding, Encoding::Utf16le | Encoding::Utf16be);
+    if !is_utf16 && is_binary(&contents) {
+        return Ok(None);
+    }
+
+    let decoded = match encoding {
+        Encoding::Auto => {
+            let contents = contents.strip_prefix(UTF8_BOM).unwrap_or(&contents);
+            String::from_utf8_lossy(contents).into_owned()
+        }
+        Encoding::Utf8 => {
+            let contents = match contents.strip_prefix(UTF8_BOM) {
+                Some(contents) => contents.to_vec(),
+                None => contents,
+            };
+            String::from_utf8(contents)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        }
+        Encoding::Utf16le => decode_utf16(
+            contents.strip_prefix(UTF16LE_BOM).unwrap_or(&contents),
+            u16::from_le_bytes,
+        ),
+        Encoding::Utf16be => decode_utf16(
+            contents.strip_prefix(UTF16BE_BOM).unwrap_or(&contents),
+            u16::from_be_bytes,
+        ),
+        Encoding::Latin1 => contents.iter().map(|&b| b as char).collect(),
+        Encoding::Windows1252 => decode_windows_1252(&contents),
+    };
+
+    Ok(Some(decoded))
+}
+
+/// Split text into lines, removing the line ending (either `\n` or `\r\n`).
+///
+/// Like `str::split`, the text after the final line ending is a line, even if it is empty.
+pub fn split_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, split_lines, Encoding};
+
+    fn decode_some(contents: &[u8], encoding: Encoding) -> String {
+        decode(contents.to_vec(), encoding).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_decode_auto() {
+        assert_eq!(decode_some(b"\xef\xbb\xbfhello", Encoding::Auto), "hello");
+        assert_eq!(decode_some(b"\xff\xfeh\x00i\x00", Encoding::Auto), "hi");
+        assert_eq!(decode_some(b"\xfe\xff\x00h\x00i", Encoding::Auto), "hi");
+        assert_eq!(decode_some(b"caf\xe9", Encoding::Auto), "caf\u{fffd}");
+        assert!(decode(b"\x7fELF\x00".to_vec(), Encoding::Auto)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_decode_explicit() {
+        assert!(decode(b"caf\xe9".to_vec(), Encoding::Utf8).is_err());
+        assert_eq!(decode_some(b"caf\xc3\xa9", Encoding::Utf8), "caf\u{e9}");
+        assert_eq!(decode_some(b"caf\xe9", Encoding::Latin1), "caf\u{e9}");
+        assert_eq!(
+            decode_some(b"\x93quoted\x94 \x80", Encoding::Windows1252),
+            "\u{201c}quoted\u{201d} \u{20ac}"
+        );
+        assert_eq!(decode_some(b"h\x00i\x00", Encoding::Utf16le), "hi");
+        assert_eq!(decode_some(b"\x00h\x00i", Encoding::Utf16be), "hi");
+    }
+
+    #[test]
+    fn test_split_lines() {
+        assert_eq!(
+            split_lines("a\r\nb\nc\r\n").collect::<Vec<&str>>(),
+            vec!["a", "b", "c", ""]
+        );
+        assert_eq!(split_lines("a\rb").collect::<Vec<&str>>(), vec!["a\rb"]);
+    }
+}
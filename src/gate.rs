@@ -0,0 +1,73 @@
+use std::fmt;
+
+use crate::cli::Cli;
+use crate::types::JsonRoot;
+
+// Exit statuses 1 (any other error, like an unreadable baseline) and 2 (invalid arguments, as
+// reported by clap) are taken, so that CI can tell these failures apart.
+
+/// Exit status when any of the `--fail-on-*` thresholds is exceeded.
+pub const EXIT_GATE_FAILED: u8 = 3;
+/// Exit status when a file couldn't be read and `--strict` is set.
+pub const EXIT_READ_ERROR: u8 = 4;
+
+/// A `--fail-on-*` threshold that was exceeded.
+#[derive(PartialEq, Debug)]
+pub enum GateFailure {
+    DuplicationRatio { ratio: f64, max: f64 },
+    Groups { groups: usize, max: usize },
+    BlockSize { size: usize, max: usize },
+}
+
+impl fmt::Display for GateFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicationRatio { ratio, max } => write!(
+                f,
+                "duplication ratio {ratio:.4} is above --fail-on-duplication-ratio {max}"
+            ),
+            Self::Groups { groups, max } => write!(
+                f,
+                "{groups} unique match(es) is above --fail-on-groups {max}"
+            ),
+            Self::BlockSize { size, max } => write!(
+                f,
+                "block of {size} line(s) is above --fail-on-block-size {max}"
+            ),
+        }
+    }
+}
+
+/// Every threshold that the results exceed.
+pub fn check(args: &Cli, root: &JsonRoot) -> Vec<GateFailure> {
+    let mut failures = Vec::new();
+
+    if let Some(max) = args.fail_on_duplication_ratio {
+        let ratio = root.summary.duplication_ratio;
+        if ratio > max {
+            failures.push(GateFailure::DuplicationRatio { ratio, max });
+        }
+    }
+
+    if let Some(max) = args.fail_on_groups {
+        let groups = root.unique_matches();
+        if groups > max {
+            failures.push(GateFailure::Groups { groups, max });
+        }
+    }
+
+    if let Some(max) = args.fail_on_block_size {
+        let size = root
+            .matches
+            .iter()
+            .flat_map(|jm| jm.blocks.values().flatten())
+            .map(|info| info.block_length)
+            .max()
+            .unwrap_or(0);
+        if size > max {
+            failures.push(GateFailure::BlockSize { size, max });
+        }
+    }
+
+    failures
+}
@@ -0,0 +1,104 @@
+use serde::Serialize;
+
+use crate::types::{portable_path, JsonRoot};
+
+const CHECK_NAME: &str = "superdiff/duplicate-code";
+
+/// A single issue in the Code Climate format, as read by GitLab's Code Quality reports.
+///
+/// Only includes the fields that GitLab uses.
+#[derive(Serialize, Debug)]
+pub struct GitlabIssue {
+    description: String,
+    check_name: &'static str,
+    fingerprint: String,
+    severity: &'static str,
+    location: GitlabLocation,
+}
+
+#[derive(Serialize, Debug)]
+struct GitlabLocation {
+    path: String,
+    lines: GitlabLines,
+}
+
+#[derive(Serialize, Debug)]
+struct GitlabLines {
+    begin: usize,
+    end: usize,
+}
+
+/// How bad a duplicated block is, from the number of lines that it duplicates.
+///
+/// A block of `block_length` lines that is part of a group of `group_size` blocks duplicates
+/// `block_length * (group_size - 1)` lines.
+pub fn severity(block_length: usize, group_size: usize) -> &'static str {
+    match block_length * group_size.saturating_sub(1) {
+        0..=9 => "info",
+        10..=24 => "minor",
+        25..=49 => "major",
+        50..=99 => "critical",
+        _ => "blocker",
+    }
+}
+
+/// One issue for every duplicated block.
+pub fn issues(root: &JsonRoot) -> Vec<GitlabIssue> {
+    let mut issues = Vec::new();
+
+    for jm in &root.matches {
+        let blocks = jm.sorted_blocks();
+
+        for &(path, nth, info) in &blocks {
+            let others = blocks
+                .iter()
+                .filter(|&&(other_path, other_nth, _)| (other_path, other_nth) != (path, nth))
+                .map(|&(other_path, _, other_info)| {
+                    format!("{}:{}", portable_path(other_path), other_info.starting_line)
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            issues.push(GitlabIssue {
+                description: format!(
+                    "Block of {} line(s) is duplicated in {} other place(s): {}",
+                    info.block_length,
+                    blocks.len() - 1,
+                    others
+                ),
+                check_name: CHECK_NAME,
+                fingerprint: jm.block_fingerprint(path, nth),
+                severity: severity(info.block_length, blocks.len()),
+                location: GitlabLocation {
+                    path: portable_path(path),
+                    lines: GitlabLines {
+                        begin: info.starting_line,
+                        end: info.starting_line + info.block_length.saturating_sub(1),
+                    },
+                },
+            });
+        }
+    }
+
+    issues
+}
+
+pub fn json(root: &JsonRoot) -> String {
+    serde_json::to_string(&issues(root)).unwrap_or("[]".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::severity;
+
+    #[test]
+    fn test_severity() {
+        assert_eq!(severity(5, 2), "info");
+        assert_eq!(severity(10, 2), "minor");
+        assert_eq!(severity(10, 3), "minor");
+        assert_eq!(severity(10, 4), "major");
+        assert_eq!(severity(30, 3), "critical");
+        assert_eq!(severity(100, 2), "blocker");
+        assert_eq!(severity(100, 1), "info");
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::cli::Cli;
+use crate::diff::{diff_shown, Change};
+use crate::types::{CompFile, FileCache, JsonBlockInfo, JsonMatch, JsonMetrics, JsonRoot, Line};
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+th { background: #eee; cursor: pointer; user-select: none; }
+th[data-order="ascending"]::after { content: " \25B2"; }
+th[data-order="descending"]::after { content: " \25BC"; }
+td.number { text-align: right; }
+.blocks { display: flex; gap: 1em; overflow-x: auto; margin-bottom: 2em; }
+.block { flex: 0 0 auto; border: 1px solid #ccc; }
+.block-header { background: #eee; padding: 0.3em 0.6em; font-family: monospace; }
+pre { margin: 0; padding: 0.3em 0.6em; }
+.line-number { color: #999; display: inline-block; min-width: 3em; user-select: none; }
+mark { background: #fd8; }
+"#;
+
+/// Makes every table with the `sortable` class sortable by clicking on its headers.
+///
+/// Cells are compared by their `data-value` if they have one, or their text otherwise.
+const SCRIPT: &str = r#"
+document.querySelectorAll("table.sortable th").forEach(th => {
+  th.addEventListener("click", () => {
+    const table = th.closest("table");
+    const body = table.tBodies[0];
+    const ascending = th.dataset.order !== "ascending";
+    table.querySelectorAll("th").forEach(other => delete other.dataset.order);
+    th.dataset.order = ascending ? "ascending" : "descending";
+
+    const key = row => {
+      const cell = row.cells[th.cellIndex];
+      return cell.dataset.value ?? cell.textContent;
+    };
+    const rows = Array.from(body.rows).sort((a, b) => {
+      const [x, y] = [key(a), key(b)];
+      const order = isNaN(x) || isNaN(y) ? x.localeCompare(y) : x - y;
+      return ascending ? order : -order;
+    });
+    body.append(...rows);
+  });
+});
+"#;
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Table cells for the total lines, duplicated lines and duplication of some metrics.
+fn format_metrics(metrics: &JsonMetrics) -> String {
+    let percentage = metrics.duplication_ratio * 100.0;
+    format!(
+        "<td class=\"number\">{}</td><td class=\"number\">{}</td><td class=\"number\" data-value=\"{percentage}\">{percentage:.1}%</td>",
+        metrics.total_lines, metrics.duplicated_lines
+    )
+}
+
+/// A line of a block as it is in the file, with the characters of its compared text that aren't
+/// in `reference` highlighted.
+fn format_line(line: &Line, reference: Option<&Line>) -> String {
+    match reference {
+        Some(reference) => diff_shown(&reference.text, &line.text, &line.raw)
+            .into_iter()
+            .filter_map(|(change, s)| match change {
+                Change::Same => Some(escape(&s)),
+                Change::Added => Some(format!("<mark>{}</mark>", escape(&s))),
+                Change::Removed => None,
+            })
+            .collect(),
+        None => escape(&line.raw),
+    }
+}
+
+/// The blocks of a match next to each other.
+///
+/// If `highlight` is set, every block is compared line by line with the first block (or the
+/// second, for the first block itself), and the characters that differ are highlighted. Lines
+/// are paired up by the lines that were compared, so lines that were stripped aren't highlighted.
+fn write_blocks(
+    html: &mut String,
+    jm: &JsonMatch,
+    files: &HashMap<&Path, &CompFile>,
+    highlight: bool,
+) {
+    let blocks: Vec<(&Path, &JsonBlockInfo, Option<&CompFile>)> = jm
+        .sorted_blocks()
+        .into_iter()
+        .map(|(path, _, info)| (path, info, files.get(path).copied()))
+        .collect();
+
+    html.push_str("<div class=\"blocks\">\n");
+    for (k, &(path, info, file)) in blocks.iter().enumerate() {
+        let reference = match k {
+            0 => blocks.get(1),
+            _ => Some(&blocks[0]),
+        }
+        .and_then(|&(_, info, file)| Some(file?.compared_block(info)))
+        .filter(|_| highlight);
+        let lines = file.map(|f| f.block(info)).unwrap_or_default();
+        let compared = file.map(|f| f.compared_indices(info)).unwrap_or_default();
+
+        writeln!(
+            html,
+            "<div class=\"block\"><div class=\"block-header\">{}:{}-{}</div><pre>",
+            escape(&path.display().to_string()),
+            info.starting_line,
+            info.starting_line + info.block_length.saturating_sub(1)
+        )
+        .unwrap();
+        for (i, line) in lines.iter().enumerate() {
+            writeln!(
+                html,
+                "<span class=\"line-number\">{}</span>{}",
+                info.starting_line + i,
+                format_line(line, compared[i].zip(reference).and_then(|(k, r)| r.get(k)))
+            )
+            .unwrap();
+        }
+        html.push_str("</pre></div>\n");
+    }
+    html.push_str("</div>\n");
+}
+
+/// Generate a single HTML page that works offline.
+///
+/// Has a table of the matches, a table of how much of each file is duplicated, and the blocks of
+/// every match side by side. When lines don't need to be equal to match (`-t` or `--normalize`),
+/// the characters that differ between blocks are highlighted.
+pub fn report(args: &Cli, root: &JsonRoot, cache: &FileCache) -> String {
+    let files = cache.by_path();
+    let highlight = args.allows_differences();
+
+    let mut html = String::new();
+    writeln!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>superdiff report</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>superdiff report</h1>\n<p>{} unique match(es) in {} file(s), found by superdiff {}.</p>",
+        root.unique_matches(),
+        cache.files.len(),
+        escape(&root.version)
+    )
+    .unwrap();
+
+    html.push_str("<h2>Matches</h2>\n<table class=\"sortable\">\n<thead><tr><th>Match</th><th>Blocks</th><th>Files</th><th>Lines</th><th>Fingerprint</th></tr></thead>\n<tbody>\n");
+    for (i, jm) in root.matches.iter().enumerate() {
+        let blocks = jm.sorted_blocks();
+        let lines = blocks
+            .iter()
+            .map(|(_, _, info)| info.block_length)
+            .max()
+            .unwrap_or(0);
+        writeln!(
+            html,
+            "<tr><td class=\"number\" data-value=\"{0}\"><a href=\"#match-{0}\">{0}</a></td><td class=\"number\">{1}</td><td class=\"number\">{2}</td><td class=\"number\">{3}</td><td><code>{4}</code></td></tr>",
+            i + 1,
+            blocks.len(),
+            jm.blocks.len(),
+            lines,
+            escape(&jm.fingerprint)
+        )
+        .unwrap();
+    }
+    html.push_str("</tbody>\n</table>\n");
+
+    html.push_str("<h2>Files</h2>\n<table class=\"sortable\">\n<thead><tr><th>File</th><th>Lines</th><th>Duplicated lines</th><th>Duplication</th><th>Blocks</th></tr></thead>\n<tbody>\n");
+    for (path, info) in &root.files {
+        let metrics = info.metrics.unwrap_or_default();
+        writeln!(
+            html,
+            "<tr><td>{}</td>{}<td class=\"number\">{}</td></tr>",
+            escape(&path.display().to_string()),
+            format_metrics(&metrics),
+            info.count_blocks
+        )
+        .unwrap();
+    }
+    writeln!(
+        html,
+        "</tbody>\n<tfoot><tr><th>Total</th>{}<td></td></tr></tfoot>\n</table>",
+        format_metrics(&root.summary)
+    )
+    .unwrap();
+
+    if !root.errors.is_empty() {
+        html.push_str("<h2>Files that could not be read</h2>\n<ul>\n");
+        for e in &root.errors {
+            writeln!(
+                html,
+                "<li><code>{}</code>: {}</li>",
+                escape(&e.path.display().to_string()),
+                escape(&e.error)
+            )
+            .unwrap();
+        }
+        html.push_str("</ul>\n");
+    }
+
+    for (i, jm) in root.matches.iter().enumerate() {
+        writeln!(html, "<h2 id=\"match-{0}\">Match {0}</h2>", i + 1).unwrap();
+        write_blocks(&mut html, jm, &files, highlight);
+    }
+
+    writeln!(html, "<script>{SCRIPT}</script>\n</body>\n</html>").unwrap();
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, format_line};
+    use crate::types::Line;
+
+    fn line(raw: &str) -> Line {
+        Line {
+            id: 0,
+            text: raw.trim().to_owned(),
+            raw: raw.to_owned(),
+            normalized: None,
+            suppressed: false,
+            number: 1,
+        }
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("a < b && \"c\""),
+            "a &lt; b &amp;&amp; &quot;c&quot;"
+        );
+    }
+
+    #[test]
+    fn test_format_line() {
+        assert_eq!(
+            format_line(&line("    x = 1 < 2"), None),
+            "    x = 1 &lt; 2"
+        );
+        assert_eq!(
+            format_line(&line("    y = 1"), Some(&line("x = 1"))),
+            "    <mark>y</mark> = 1"
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use clap::ValueEnum;
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Programming languages that files can be selected by.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum, Debug)]
+pub enum Language {
+    C,
+    Cpp,
+    Csharp,
+    Go,
+    Haskell,
+    Java,
+    Javascript,
+    Kotlin,
+    Lua,
+    Php,
+    Python,
+    Ruby,
+    Rust,
+    Shell,
+    Sql,
+    Typescript,
+}
+
+impl Language {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "c" | "h" => Some(Self::C),
+            "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => Some(Self::Cpp),
+            "cs" => Some(Self::Csharp),
+            "go" => Some(Self::Go),
+            "hs" => Some(Self::Haskell),
+            "java" => Some(Self::Java),
+            "js" | "jsx" | "mjs" | "cjs" => Some(Self::Javascript),
+            "kt" | "kts" => Some(Self::Kotlin),
+            "lua" => Some(Self::Lua),
+            "php" => Some(Self::Php),
+            "py" | "pyw" => Some(Self::Python),
+            "rb" => Some(Self::Ruby),
+            "rs" => Some(Self::Rust),
+            "sh" | "bash" | "zsh" => Some(Self::Shell),
+            "sql" => Some(Self::Sql),
+            "ts" | "tsx" => Some(Self::Typescript),
+            _ => None,
+        }
+    }
+
+    pub fn from_interpreter(interpreter: &str) -> Option<Self> {
+        // Strip version numbers, e.g. `python3.11`
+        match interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+            "python" => Some(Self::Python),
+            "ruby" => Some(Self::Ruby),
+            "node" | "nodejs" => Some(Self::Javascript),
+            "sh" | "bash" | "zsh" | "dash" | "ksh" => Some(Self::Shell),
+            "lua" => Some(Self::Lua),
+            "php" => Some(Self::Php),
+            "runghc" | "runhaskell" => Some(Self::Haskell),
+            _ => None,
+        }
+    }
+
+    /// Find the language from a shebang line (e.g. `#!/usr/bin/env python`).
+    pub fn from_shebang(line: &str) -> Option<Self> {
+        let mut words = line.strip_prefix("#!")?.split_whitespace();
+        let mut interpreter = Path::new(words.next()?).file_name()?.to_str()?;
+
+        if interpreter == "env" {
+            interpreter = words.find(|word| !word.starts_with('-'))?;
+        }
+
+        Self::from_interpreter(interpreter)
+    }
+
+    /// Find the language of a file from its extension, or its shebang if it has no known
+    /// extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        if let Some(language) = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(Self::from_extension)
+        {
+            return Some(language);
+        }
+
+        let mut first_line = String::new();
+        BufReader::new(File::open(path).ok()?)
+            .read_line(&mut first_line)
+            .ok()?;
+
+        Self::from_shebang(&first_line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Language;
+    use std::path::Path;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            Language::from_path(Path::new("src/main.rs")),
+            Some(Language::Rust)
+        );
+        assert_eq!(
+            Language::from_path(Path::new("examples/really-bad-code.py")),
+            Some(Language::Python)
+        );
+        assert_eq!(Language::from_path(Path::new("README.md")), None);
+    }
+
+    #[test]
+    fn test_from_shebang() {
+        assert_eq!(
+            Language::from_shebang("#!/usr/bin/env python3"),
+            Some(Language::Python)
+        );
+        assert_eq!(
+            Language::from_shebang("#!/usr/bin/env -S node --harmony"),
+            Some(Language::Javascript)
+        );
+        assert_eq!(
+            Language::from_shebang("#!/bin/bash -e"),
+            Some(Language::Shell)
+        );
+        assert_eq!(Language::from_shebang("#!/usr/bin/perl"), None);
+        assert_eq!(Language::from_shebang("# python"), None);
+    }
+}
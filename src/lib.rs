@@ -1,6 +1,24 @@
+pub mod baseline;
 pub mod cli;
 pub mod comp;
+pub mod config;
+pub mod cpd;
+pub mod diff;
+pub mod encoding;
+pub mod gate;
+pub mod gitlab;
+pub mod html;
+pub mod lang;
 pub mod math;
+pub mod normalize;
 pub mod printer;
+pub mod sarif;
+pub mod snap;
+pub mod snippet;
+pub mod strip;
+pub mod suffix;
+pub mod suppress;
+#[cfg(feature = "syntax")]
+pub mod syntax;
 pub mod types;
 pub mod threadpool;
@@ -1,16 +1,57 @@
-use clap::Parser;
+use std::env;
+use std::process::ExitCode;
 use superdiff::*;
 
-fn main() {
-    let mut args = cli::Cli::parse();
+fn main() -> ExitCode {
+    let dir = env::current_dir().unwrap_or_default();
+    let mut args = match cli::Cli::load_from(env::args_os(), &dir) {
+        Ok(args) => args,
+        Err(e) => e.exit(),
+    };
     if args.files_from_stdin() {
         args.populate_files_from_stdin();
     }
+    args.expand_directories();
+    args.filter_files();
     args.print();
 
-    let mut pool = threadpool::ThreadPool::from(args.clone());
+    let print_args = args.clone();
+    let mut pool = threadpool::ThreadPool::from(args.clone())
+        .with_pair_callback(move |pair| printer::pair(&print_args, pair));
+    if let Some(path) = &args.baseline {
+        match baseline::Baseline::read(path) {
+            Ok(baseline) => pool = pool.with_baseline(baseline),
+            Err(e) => {
+                eprintln!("error: could not read baseline {}: {e}", path.display());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
     let matches = pool.run_and_get_results();
 
-    printer::matches(&args, &matches);
+    if let Some(path) = &args.write_baseline {
+        if let Err(e) = pool.found().write(path) {
+            eprintln!("error: could not write baseline {}: {e}", path.display());
+            return ExitCode::FAILURE;
+        }
+    }
+
+    printer::errors(&args, &matches);
+    printer::matches(&args, &matches, &pool.cache());
+    printer::summary(&args, &matches);
     printer::conclusion(&args, &matches);
+
+    let failures = gate::check(&args, &matches);
+    for failure in &failures {
+        eprintln!("error: {failure}");
+    }
+    if !failures.is_empty() {
+        return ExitCode::from(gate::EXIT_GATE_FAILED);
+    }
+
+    if args.strict && !matches.errors.is_empty() {
+        return ExitCode::from(gate::EXIT_READ_ERROR);
+    }
+
+    ExitCode::SUCCESS
 }
@@ -19,9 +19,24 @@ pub fn combinations(n: usize, k: usize) -> usize {
     }
 }
 
+/// Starting value for `fnv1a`.
+pub const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// 64-bit FNV-1a hash of some bytes, continuing from `hash` (`FNV_OFFSET_BASIS` to start a new
+/// one).
+///
+/// Unlike `DefaultHasher`, this is guaranteed to give the same result across runs and versions,
+/// so it can be used for fingerprints that are stored elsewhere.
+pub fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(FNV_PRIME)
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{combinations, factorial};
+    use super::{combinations, factorial, fnv1a, FNV_OFFSET_BASIS};
 
     #[test]
     fn test_factorial() {
@@ -35,4 +50,15 @@ mod tests {
     fn test_combinations() {
         assert_eq!(combinations(9, 2), 36);
     }
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"foobar"), 0x85944171f73967e8);
+        assert_eq!(
+            fnv1a(fnv1a(FNV_OFFSET_BASIS, b"foo"), b"bar"),
+            fnv1a(FNV_OFFSET_BASIS, b"foobar")
+        );
+    }
 }
@@ -0,0 +1,174 @@
+use clap::ValueEnum;
+
+/// Parts of a line that can be replaced before comparison.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+pub enum Normalization {
+    /// Replace names of variables, functions, types, etc. with `$id`
+    ///
+    /// Keywords are left alone.
+    Identifiers,
+    /// Replace numbers, strings and characters with `$lit`
+    Literals,
+}
+
+const IDENTIFIER_PLACEHOLDER: &str = "$id";
+const LITERAL_PLACEHOLDER: &str = "$lit";
+
+/// Keywords of commonly used languages, which are never treated as identifiers.
+#[rustfmt::skip]
+const KEYWORDS: &[&str] = &[
+    "abstract", "and", "as", "assert", "async", "await", "bool", "boolean", "break", "byte", "case",
+    "catch", "char", "class", "const", "continue", "def", "default", "del", "do", "double", "elif",
+    "else", "enum", "except", "export", "extends", "extern", "false", "final", "finally", "float",
+    "fn", "for", "from", "func", "function", "global", "go", "if", "impl", "implements", "import",
+    "in", "instanceof", "int", "interface", "is", "lambda", "let", "long", "loop", "match", "mod",
+    "move", "mut", "new", "nil", "None", "nonlocal", "not", "null", "or", "package", "pass",
+    "private", "protected", "pub", "public", "raise", "ref", "return", "self", "Self", "short",
+    "static", "struct", "super", "switch", "this", "throw", "throws", "trait", "True", "true",
+    "False", "try", "type", "typeof", "undefined", "unsafe", "use", "var", "void", "where", "while",
+    "with", "yield",
+];
+
+/// A piece of a line.
+#[derive(PartialEq, Eq, Debug)]
+pub enum Token<'a> {
+    Identifier(&'a str),
+    Keyword(&'a str),
+    Literal(&'a str),
+    /// Anything else that isn't whitespace (operators, brackets, etc.), one character at a time.
+    Symbol(&'a str),
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '$'
+}
+
+fn is_identifier_continue(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Byte length of a string literal that starts with `quote`, up to and including the closing
+/// quote (or the end of the line if it is never closed).
+fn string_literal_length(s: &str, quote: char) -> usize {
+    let mut chars = s.char_indices().skip(1);
+
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == quote {
+            return i + c.len_utf8();
+        }
+    }
+
+    s.len()
+}
+
+/// Split a line into tokens, loosely following the lexical rules of C-like languages and Python.
+///
+/// This doesn't need to be exact; it only needs to be consistent between lines.
+pub fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = line;
+
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let length = if is_identifier_start(c) {
+            rest.find(|c| !is_identifier_continue(c))
+                .unwrap_or(rest.len())
+        } else if c.is_ascii_digit() {
+            rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len())
+        } else if c == '"' || c == '\'' || c == '`' {
+            string_literal_length(rest, c)
+        } else {
+            c.len_utf8()
+        };
+
+        let (token, remaining) = rest.split_at(length);
+        tokens.push(if is_identifier_start(c) {
+            if KEYWORDS.contains(&token) {
+                Token::Keyword(token)
+            } else {
+                Token::Identifier(token)
+            }
+        } else if c.is_ascii_digit() || c == '"' || c == '\'' || c == '`' {
+            Token::Literal(token)
+        } else {
+            Token::Symbol(token)
+        });
+        rest = remaining;
+    }
+
+    tokens
+}
+
+/// Replace the given kinds of tokens in a line with placeholders.
+///
+/// Tokens are joined with single spaces, so differences in whitespace are ignored as well.
+pub fn normalize(line: &str, normalizations: &[Normalization]) -> String {
+    tokenize(line)
+        .into_iter()
+        .map(|token| match token {
+            Token::Identifier(_) if normalizations.contains(&Normalization::Identifiers) => {
+                IDENTIFIER_PLACEHOLDER
+            }
+            Token::Literal(_) if normalizations.contains(&Normalization::Literals) => {
+                LITERAL_PLACEHOLDER
+            }
+            Token::Identifier(s) | Token::Keyword(s) | Token::Literal(s) | Token::Symbol(s) => s,
+        })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize, tokenize, Normalization, Token};
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize(r#"let x1 = f("a \" b", 4.5);"#),
+            vec![
+                Token::Keyword("let"),
+                Token::Identifier("x1"),
+                Token::Symbol("="),
+                Token::Identifier("f"),
+                Token::Symbol("("),
+                Token::Literal(r#""a \" b""#),
+                Token::Symbol(","),
+                Token::Literal("4.5"),
+                Token::Symbol(")"),
+                Token::Symbol(";"),
+            ]
+        );
+        assert_eq!(tokenize("   "), vec![]);
+        assert_eq!(tokenize("'unclosed"), vec![Token::Literal("'unclosed")]);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let both = [Normalization::Identifiers, Normalization::Literals];
+
+        assert_eq!(
+            normalize("self.alpha = 12", &both),
+            normalize("self.gamma   =   'sixteen'", &both)
+        );
+        assert_eq!(
+            normalize("for i in range(20):", &[Normalization::Identifiers]),
+            "for $id in $id ( 20 ) :"
+        );
+        assert_eq!(
+            normalize("for i in range(20):", &[Normalization::Literals]),
+            "for i in range ( $lit ) :"
+        );
+        assert_ne!(
+            normalize("if x: return y", &both),
+            normalize("while x: return y", &both)
+        );
+    }
+}
@@ -1,6 +1,10 @@
 use crate::cli::{Cli, ReportingMode};
-use crate::math::combinations;
-use crate::types::JsonRoot;
+use crate::cpd;
+use crate::gitlab;
+use crate::html;
+use crate::sarif::SarifLog;
+use crate::snippet;
+use crate::types::{FileCache, JsonLine, JsonRoot, Match};
 
 use std::thread;
 use std::sync::mpsc;
@@ -8,35 +12,80 @@ use std::sync::mpsc;
 /// Spawn a thread that prints progress Text
 ///
 /// Will not spawn any thread at all if `verbose` is not set.
-pub fn spawn_processing_text(args: &Cli, rx: mpsc::Receiver<bool>) {
+pub fn spawn_processing_text(args: &Cli, total: usize, rx: mpsc::Receiver<bool>) {
     if !args.verbose {
         return;
     }
 
-    let total = combinations(args.files.len(), 2) + args.files.len();
     thread::spawn(move || {
-        let mut i = 1usize;
-        for _ in rx {
+        for (i, _) in (1usize..).zip(rx) {
             let percentage = i * 100 / total;
             eprint!("{percentage}% completed\r");
-
-            i += 1;
         }
         eprintln!();
     });
 }
 
-pub fn matches(args: &Cli, matches: &JsonRoot) {
+/// Print a pair of matching blocks as soon as it is found.
+///
+/// Only used for `ReportingMode::Jsonl`.
+pub fn pair(args: &Cli, pair: &(Match, Match)) {
+    if args.reporting_mode == ReportingMode::Jsonl {
+        println!("{}", JsonLine::from_pair(pair).json());
+    }
+}
+
+pub fn matches(args: &Cli, matches: &JsonRoot, cache: &FileCache) {
     match args.reporting_mode {
         ReportingMode::Json => {
             println!("{}", matches.json());
         }
+        ReportingMode::Jsonl => {
+            println!("{}", JsonLine::Result(matches).json());
+        }
+        ReportingMode::Sarif => {
+            println!("{}", SarifLog::from(matches).json());
+        }
+        ReportingMode::CpdXml => {
+            println!("{}", cpd::report(matches, cache));
+        }
+        ReportingMode::Gitlab => {
+            println!("{}", gitlab::json(matches));
+        }
+        ReportingMode::Html => {
+            println!("{}", html::report(args, matches, cache));
+        }
+        ReportingMode::Text if args.snippets => {
+            println!("{}", snippet::report(args, matches, cache));
+        }
         ReportingMode::Text => {
             println!("{matches}");
         }
     }
 }
 
+/// Print the table of duplication metrics (see `--summary`).
+///
+/// Only used for `ReportingMode::Text`.
+pub fn summary(args: &Cli, matches: &JsonRoot) {
+    if args.reporting_mode == ReportingMode::Text && args.summary {
+        println!("{}", matches.summary_table());
+    }
+}
+
+/// Warn about files that couldn't be read.
+///
+/// Only used for `ReportingMode::Text`; other modes include the errors in their output.
+pub fn errors(args: &Cli, matches: &JsonRoot) {
+    if args.reporting_mode != ReportingMode::Text {
+        return;
+    }
+
+    for e in &matches.errors {
+        eprintln!("warning: could not read {}: {}", e.path.display(), e.error);
+    }
+}
+
 pub fn conclusion(args: &Cli, matches: &JsonRoot) {
     if args.verbose {
         eprintln!(
@@ -44,5 +93,23 @@ pub fn conclusion(args: &Cli, matches: &JsonRoot) {
             matches.unique_matches(),
             args.files.len()
         );
+
+        if matches.known_matches > 0 {
+            eprintln!(
+                "{} match(es) were left out because they are in the baseline.",
+                matches.known_matches
+            );
+        }
+
+        if matches.suppressed_lines > 0 {
+            eprintln!(
+                "{} line(s) were suppressed by superdiff:ignore comments.",
+                matches.suppressed_lines
+            );
+        }
+
+        if !matches.errors.is_empty() {
+            eprintln!("{} file(s) could not be read.", matches.errors.len());
+        }
     }
 }
@@ -0,0 +1,213 @@
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::types::{portable_path, JsonBlockInfo, JsonRoot};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+const RULE_ID: &str = "duplicate-code";
+/// Key of the fingerprint in `partialFingerprints`, versioned in case the hash ever changes.
+const FINGERPRINT_KEY: &str = "superdiff/duplicateBlock/v1";
+
+/// A SARIF 2.1.0 log with a single run.
+///
+/// Only includes the parts of the format that we need.
+#[derive(Serialize, Debug)]
+pub struct SarifLog {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<SarifRun>,
+}
+
+#[derive(Serialize, Debug)]
+struct SarifRun {
+    tool: SarifTool,
+    results: Vec<SarifResult>,
+    invocations: Vec<SarifInvocation>,
+}
+
+#[derive(Serialize, Debug)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SarifDriver {
+    name: &'static str,
+    version: String,
+    information_uri: &'static str,
+    rules: Vec<SarifRule>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SarifRule {
+    id: &'static str,
+    short_description: SarifMessage,
+}
+
+#[derive(Serialize, Debug)]
+struct SarifMessage {
+    text: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: &'static str,
+    level: &'static str,
+    message: SarifMessage,
+    locations: Vec<SarifLocation>,
+    related_locations: Vec<SarifLocation>,
+    partial_fingerprints: HashMap<&'static str, String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<usize>,
+    physical_location: SarifPhysicalLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<SarifMessage>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation {
+    artifact_location: SarifArtifactLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    region: Option<SarifRegion>,
+}
+
+#[derive(Serialize, Debug)]
+struct SarifArtifactLocation {
+    uri: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line: usize,
+    end_line: usize,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SarifInvocation {
+    execution_successful: bool,
+    tool_execution_notifications: Vec<SarifNotification>,
+}
+
+#[derive(Serialize, Debug)]
+struct SarifNotification {
+    level: &'static str,
+    message: SarifMessage,
+    locations: Vec<SarifLocation>,
+}
+
+fn location(path: &Path, info: Option<&JsonBlockInfo>) -> SarifLocation {
+    SarifLocation {
+        id: None,
+        physical_location: SarifPhysicalLocation {
+            artifact_location: SarifArtifactLocation {
+                uri: portable_path(path),
+            },
+            region: info.map(|info| SarifRegion {
+                start_line: info.starting_line,
+                end_line: info.starting_line + info.block_length.saturating_sub(1),
+            }),
+        },
+        message: None,
+    }
+}
+
+impl From<&JsonRoot> for SarifLog {
+    fn from(root: &JsonRoot) -> Self {
+        let mut results = Vec::new();
+
+        for jm in &root.matches {
+            let blocks = jm.sorted_blocks();
+
+            for (i, &(path, nth, info)) in blocks.iter().enumerate() {
+                let related_locations = blocks
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .map(|(j, &(other_path, _, other_info))| SarifLocation {
+                        id: Some(j),
+                        message: Some(SarifMessage {
+                            text: format!("Duplicate at line {}", other_info.starting_line),
+                        }),
+                        ..location(other_path, Some(other_info))
+                    })
+                    .collect();
+
+                results.push(SarifResult {
+                    rule_id: RULE_ID,
+                    level: "warning",
+                    message: SarifMessage {
+                        text: format!(
+                            "Block of {} line(s) is duplicated in {} other place(s).",
+                            info.block_length,
+                            blocks.len() - 1
+                        ),
+                    },
+                    locations: vec![location(path, Some(info))],
+                    related_locations,
+                    partial_fingerprints: HashMap::from([(
+                        FINGERPRINT_KEY,
+                        jm.block_fingerprint(path, nth),
+                    )]),
+                });
+            }
+        }
+
+        let tool_execution_notifications = root
+            .errors
+            .iter()
+            .map(|e| SarifNotification {
+                level: "warning",
+                message: SarifMessage {
+                    text: format!("Could not read file: {}", e.error),
+                },
+                locations: vec![location(&e.path, None)],
+            })
+            .collect();
+
+        Self {
+            schema: SCHEMA,
+            version: SARIF_VERSION,
+            runs: vec![SarifRun {
+                tool: SarifTool {
+                    driver: SarifDriver {
+                        name: "superdiff",
+                        version: root.version.clone(),
+                        information_uri: env!("CARGO_PKG_REPOSITORY"),
+                        rules: vec![SarifRule {
+                            id: RULE_ID,
+                            short_description: SarifMessage {
+                                text: "Duplicated block of code".to_owned(),
+                            },
+                        }],
+                    },
+                },
+                results,
+                invocations: vec![SarifInvocation {
+                    execution_successful: true,
+                    tool_execution_notifications,
+                }],
+            }],
+        }
+    }
+}
+
+impl SarifLog {
+    pub fn json(&self) -> String {
+        serde_json::to_string(&self).unwrap_or("{}".to_owned())
+    }
+}
@@ -0,0 +1,336 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::cli::Cli;
+use crate::comp::update_matches;
+use crate::lang::Language;
+use crate::strip::strip_comments;
+use crate::types::{FileCache, Line, Match, Matches, MatchesLookup};
+
+/// Keywords that continue the statement before them, so a block can't start or end next to them.
+const CONTINUATIONS: &[&str] = &[
+    "else", "elif", "except", "finally", "catch", "case", "default",
+];
+
+/// Parts of some lines, as the offset of their first line and their number of lines.
+type Parts = HashSet<(usize, usize)>;
+
+/// How the structure of a file is written.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Style {
+    /// With balanced brackets, like C
+    Braces,
+    /// With indentation, like Python
+    Indentation,
+}
+
+impl Style {
+    /// Find the style from the language of the file, or from whether it has curly braces at all.
+    pub fn of(path: &Path, lines: &[Line]) -> Self {
+        match Language::from_path(path) {
+            Some(Language::Python | Language::Haskell) => Self::Indentation,
+            Some(
+                Language::C
+                | Language::Cpp
+                | Language::Csharp
+                | Language::Go
+                | Language::Java
+                | Language::Javascript
+                | Language::Kotlin
+                | Language::Php
+                | Language::Rust
+                | Language::Typescript,
+            ) => Self::Braces,
+            _ if lines.iter().any(|line| line.raw.contains('{')) => Self::Braces,
+            _ => Self::Indentation,
+        }
+    }
+}
+
+/// What snapping needs to know about a line.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Shape {
+    blank: bool,
+    indent: usize,
+    /// Opening brackets minus closing brackets.
+    net: isize,
+    /// The lowest that `net` goes partway through the line (at most 0).
+    dip: isize,
+    /// Whether the line starts with a keyword like `else`, after any closing brackets.
+    continues: bool,
+}
+
+impl Shape {
+    /// The shape of a line, given the line without its comments.
+    fn new(code: &str) -> Self {
+        let (mut net, mut dip) = (0, 0);
+        for c in code.chars() {
+            match c {
+                '{' | '(' | '[' => net += 1,
+                '}' | ')' | ']' => {
+                    net -= 1;
+                    dip = std::cmp::min(dip, net);
+                }
+                _ => {}
+            }
+        }
+
+        let first_word = code
+            .trim_start_matches(|c: char| c.is_whitespace() || matches!(c, '}' | ')' | ']'))
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .next()
+            .unwrap_or("");
+
+        Self {
+            blank: code.trim().is_empty(),
+            indent: code.len() - code.trim_start().len(),
+            net,
+            dip,
+            continues: CONTINUATIONS.contains(&first_word),
+        }
+    }
+}
+
+/// The shapes of every line of a file.
+fn shapes(path: &Path, lines: &[Line]) -> Vec<Shape> {
+    let raw: Vec<&str> = lines.iter().map(|line| line.raw.as_str()).collect();
+    let code = match Language::from_path(path) {
+        Some(language) => strip_comments(&raw, language),
+        None => raw.iter().map(|&line| line.to_owned()).collect(),
+    };
+
+    code.iter().map(|code| Shape::new(code)).collect()
+}
+
+/// Whether a block can end on line `end`, i.e. the statement it is in doesn't go on after it.
+fn is_end(style: Style, shapes: &[Shape], start: usize, end: usize) -> bool {
+    if shapes[end].blank {
+        return false;
+    }
+
+    match shapes[end + 1..].iter().find(|shape| !shape.blank) {
+        None => true,
+        Some(next) => match style {
+            Style::Braces => !next.continues,
+            Style::Indentation => {
+                let indent = shapes[start].indent;
+                next.indent < indent || (next.indent == indent && !next.continues)
+            }
+        },
+    }
+}
+
+/// Every part of the lines `start..end` that starts and ends on a syntactic boundary.
+///
+/// With braces, every bracket that is opened in the part is closed in it, and no bracket from
+/// before it is closed. With indentation, no line is indented less than the first line, and the
+/// line after the part is indented no more than the first line.
+///
+/// Returns the offset of the first line from `start` and the number of lines of each part.
+fn parts(style: Style, shapes: &[Shape], (start, end): (usize, usize)) -> Parts {
+    let mut parts = HashSet::new();
+
+    for first in start..end {
+        if shapes[first].blank || shapes[first].continues {
+            continue;
+        }
+
+        let mut depth = 0;
+        for last in first..end {
+            let shape = &shapes[last];
+            match style {
+                Style::Braces => {
+                    if depth + shape.dip < 0 {
+                        break;
+                    }
+                    depth += shape.net;
+                }
+                Style::Indentation => {
+                    if !shape.blank && shape.indent < shapes[first].indent {
+                        break;
+                    }
+                }
+            }
+
+            let is_balanced = style == Style::Indentation || depth == 0;
+            if is_balanced && is_end(style, shapes, first, last) {
+                parts.insert((first - start, last - first + 1));
+            }
+        }
+    }
+
+    parts
+}
+
+/// The longest of some parts, or the first one if several are as long.
+fn longest(parts: &Parts) -> Option<(usize, usize)> {
+    parts
+        .iter()
+        .copied()
+        .max_by_key(|&(offset, size)| (size, Reverse(offset)))
+}
+
+/// The parts of some lines on a syntactic boundary (see `parts`) as ranges of the compared lines.
+///
+/// Parts that start or end on lines that weren't compared (see `--strip`) lose them, since those
+/// lines don't line up with anything in the other blocks.
+fn compared_parts(parts: Parts, start: usize, compared: &[Line]) -> Parts {
+    parts
+        .into_iter()
+        .filter_map(|(offset, size)| {
+            let first = start + offset + 1;
+            let first_index = compared.partition_point(|line| line.number < first);
+            let end_index = compared.partition_point(|line| line.number < first + size);
+            (first_index < end_index).then(|| (first_index, end_index - first_index))
+        })
+        .collect()
+}
+
+/// Trim every block so that it starts and ends on a syntactic boundary (see `--snap`).
+///
+/// The blocks of a group are trimmed the same way, to the longest part of the compared lines that
+/// is on a boundary in every copy, so that they still line up. Blocks are only ever trimmed, since
+/// extending them would add lines that don't match. Groups that end up shorter than
+/// `block_threshold`, or with less than 2 blocks, are left out, and groups that end up with the
+/// same block are merged.
+///
+/// Groups with gap lines (see `--max-gap`) are left as they are, since their compared lines don't
+/// line up one to one.
+pub fn snap_matches(args: &Cli, cache: &FileCache, matches: &Matches) -> Matches {
+    let files = cache.by_path();
+    let mut shapes_by_path: HashMap<&Path, (Style, Vec<Shape>)> = HashMap::new();
+
+    // The compared lines of a block, and its parts on a boundary as ranges of them
+    let mut parts_of = |m: &Match| -> Option<(&[Line], Parts)> {
+        let file = files.get(m.file.as_path())?;
+        let (style, shapes) = shapes_by_path
+            .entry(file.file.as_path())
+            .or_insert_with(|| {
+                (
+                    Style::of(&file.file, &file.source),
+                    shapes(&file.file, &file.source),
+                )
+            });
+
+        let start = m.line - 1;
+        let end = std::cmp::min(start + m.size, shapes.len());
+        let first = file.lines.partition_point(|line| line.number < m.line);
+        let end_index = file.lines.partition_point(|line| line.number <= end);
+        let compared = &file.lines[first..end_index];
+
+        let parts = parts(*style, shapes, (start, end));
+        Some((compared, compared_parts(parts, start, compared)))
+    };
+
+    let mut where_is_match = MatchesLookup(HashMap::new());
+    let mut snapped = Matches(HashMap::new());
+    for (key, others) in &matches.0 {
+        let group = std::iter::once(key).chain(others);
+        if group.clone().any(|m| m.gap_lines > 0) {
+            for other in others {
+                update_matches(
+                    (key.clone(), other.clone()),
+                    (&mut where_is_match, &mut snapped),
+                );
+            }
+            continue;
+        }
+
+        let mut blocks = vec![];
+        let mut common: Option<Parts> = None;
+        for m in group {
+            let Some((compared, parts)) = parts_of(m) else {
+                continue;
+            };
+            common = Some(match common {
+                Some(common) => &common & &parts,
+                None => parts,
+            });
+            blocks.push((m, compared));
+        }
+
+        let Some((offset, size)) = common.as_ref().and_then(longest) else {
+            continue;
+        };
+        if size < args.block_threshold {
+            continue;
+        }
+
+        let mut blocks: Vec<Match> = blocks
+            .into_iter()
+            .map(|(m, compared)| {
+                let (first, last) = (&compared[offset], &compared[offset + size - 1]);
+                Match {
+                    file: m.file.clone(),
+                    line: first.number,
+                    size: last.number - first.number + 1,
+                    gap_lines: m.gap_lines,
+                }
+            })
+            .collect();
+        blocks.sort_by(|a, b| (&a.file, a.line, a.size).cmp(&(&b.file, b.line, b.size)));
+        blocks.dedup();
+
+        for other in blocks.iter().skip(1) {
+            update_matches(
+                (blocks[0].clone(), other.clone()),
+                (&mut where_is_match, &mut snapped),
+            );
+        }
+    }
+
+    snapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{longest, parts, Shape, Style};
+
+    fn shapes(lines: &[&str]) -> Vec<Shape> {
+        lines.iter().map(|line| Shape::new(line)).collect()
+    }
+
+    fn snap_lines(style: Style, shapes: &[Shape], range: (usize, usize)) -> Option<(usize, usize)> {
+        longest(&parts(style, shapes, range)).map(|(offset, size)| (range.0 + offset, size))
+    }
+
+    #[test]
+    fn test_snap_braces() {
+        let lines = shapes(&[
+            "        x += 1;",
+            "    }",
+            "    if (x > 0) {",
+            "        y = f(x,",
+            "              x);",
+            "    } else {",
+            "        y = 0;",
+            "    }",
+            "    while (y) {",
+            "        y--;",
+        ]);
+
+        assert_eq!(snap_lines(Style::Braces, &lines, (0, 10)), Some((2, 6)));
+        // Can't end before the `else`
+        assert_eq!(snap_lines(Style::Braces, &lines, (2, 6)), None);
+    }
+
+    #[test]
+    fn test_snap_indentation() {
+        let lines = shapes(&[
+            "        return x",
+            "",
+            "    def f(self):",
+            "        if self.x:",
+            "            return 1",
+            "        else:",
+            "            return 2",
+            "",
+            "    def g(self):",
+            "        pass",
+        ]);
+
+        assert_eq!(snap_lines(Style::Indentation, &lines, (1, 8)), Some((2, 5)));
+        assert_eq!(snap_lines(Style::Indentation, &lines, (3, 5)), Some((4, 1)));
+    }
+}
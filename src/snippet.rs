@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::cli::Cli;
+use crate::diff::{diff_shown, Change};
+use crate::types::{format_file, CompFile, FileCache, JsonBlockInfo, JsonMatch, JsonRoot, Line};
+
+const BOLD: &str = "1";
+const DIM: &str = "2";
+const RED: &str = "31";
+const GREEN: &str = "32";
+const CYAN: &str = "36";
+
+/// Wraps text in ANSI escape codes, or leaves it alone if colors are off.
+struct Painter {
+    color: bool,
+}
+
+impl Painter {
+    fn paint(&self, code: &str, s: &str) -> String {
+        if self.color && !s.is_empty() {
+            format!("\x1b[{code}m{s}\x1b[0m")
+        } else {
+            s.to_owned()
+        }
+    }
+
+    /// An inline diff of a line against the same line of another block.
+    ///
+    /// The line is shown as it is in the file, but only the text that was compared is diffed.
+    /// Without colors, this uses the same markers as `git diff --word-diff=plain`.
+    fn diff(&self, reference: &Line, line: &Line) -> String {
+        diff_shown(&reference.text, &line.text, &line.raw)
+            .into_iter()
+            .map(|(change, s)| match (change, self.color) {
+                (Change::Same, _) => s,
+                (Change::Removed, true) => self.paint(RED, &s),
+                (Change::Added, true) => self.paint(GREEN, &s),
+                (Change::Removed, false) => format!("[-{s}-]"),
+                (Change::Added, false) => format!("{{+{s}+}}"),
+            })
+            .collect()
+    }
+}
+
+/// Print the lines of a block, along with `context` lines around it.
+///
+/// Lines of the block are marked with `>` and context lines with `|`. If there's a `reference`
+/// block (of compared lines), each compared line is shown as an inline diff against the line
+/// that was compared at the same position in the reference.
+fn format_block(
+    args: &Cli,
+    painter: &Painter,
+    file: &CompFile,
+    info: &JsonBlockInfo,
+    reference: Option<&[Line]>,
+) -> String {
+    let block = info.starting_line - 1..info.starting_line - 1 + info.block_length;
+    let start = block.start.saturating_sub(args.context);
+    let end = std::cmp::min(block.end + args.context, file.source.len());
+    let width = end.to_string().len();
+    let compared = file.compared_indices(info);
+
+    let mut snippet = String::new();
+    for (i, line) in file.source.iter().enumerate().take(end).skip(start) {
+        let number = painter.paint(CYAN, &format!("{:>width$}", i + 1));
+        if !block.contains(&i) {
+            writeln!(snippet, "{number} | {}", painter.paint(DIM, &line.raw)).unwrap();
+            continue;
+        }
+
+        let k = compared.get(i - block.start).copied().flatten();
+        let text = match k.zip(reference).and_then(|(k, r)| r.get(k)) {
+            Some(reference) => painter.diff(reference, line),
+            None => line.raw.clone(),
+        };
+        writeln!(snippet, "{number} {} {text}", painter.paint(BOLD, ">")).unwrap();
+    }
+
+    snippet
+}
+
+/// Like `impl Display for JsonMatch`, but with the lines of every block.
+///
+/// The first block (by path and line) represents the match. If lines can match without being
+/// equal, every other block is shown as an inline diff against it.
+fn format_match(
+    args: &Cli,
+    painter: &Painter,
+    files: &HashMap<&Path, &CompFile>,
+    jm: &JsonMatch,
+) -> String {
+    let blocks = jm.sorted_blocks();
+    let representative = blocks
+        .first()
+        .and_then(|&(path, _, info)| Some(files.get(path)?.compared_block(info)))
+        .filter(|_| args.allows_differences());
+
+    let mut sections = Vec::new();
+    for (path, infos) in &jm.blocks {
+        let path = path.as_path();
+        let mut section = painter.paint(BOLD, &format_file(path, infos));
+        if let Some(file) = files.get(path) {
+            for (nth, info) in infos.iter().enumerate() {
+                let is_representative = (path, nth) == (blocks[0].0, blocks[0].1);
+                let reference = representative.filter(|_| !is_representative);
+
+                section.push_str("\n\n");
+                section.push_str(
+                    format_block(args, painter, file, info, reference).trim_end_matches('\n'),
+                );
+            }
+        }
+        sections.push(section);
+    }
+
+    format!("=== MATCH ===\n{}\n", sections.join("\n---\n"))
+}
+
+/// Text output with the lines of every block (see `--snippets`).
+pub fn report(args: &Cli, root: &JsonRoot, cache: &FileCache) -> String {
+    let files = cache.by_path();
+    let painter = Painter {
+        color: args.uses_color(),
+    };
+
+    root.matches
+        .iter()
+        .map(|jm| format_match(args, &painter, &files, jm))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Painter;
+    use crate::types::Line;
+
+    fn line(text: &str) -> Line {
+        Line {
+            id: 0,
+            text: text.to_owned(),
+            raw: text.to_owned(),
+            normalized: None,
+            suppressed: false,
+            number: 1,
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let (a, b) = (line("x = 12"), line("x = 16"));
+
+        assert_eq!(Painter { color: false }.diff(&a, &b), "x = 1[-2-]{+6+}");
+        assert_eq!(
+            Painter { color: true }.diff(&a, &b),
+            "x = 1\x1b[31m2\x1b[0m\x1b[32m6\x1b[0m"
+        );
+    }
+}
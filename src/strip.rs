@@ -0,0 +1,209 @@
+use clap::ValueEnum;
+
+use crate::lang::Language;
+
+/// Lines that can be left out of comparisons.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+pub enum Strip {
+    /// Leave out comments, as far as the language of the file is known
+    ///
+    /// Lines that only have a comment are left out, and comments at the end of a line are
+    /// ignored.
+    Comments,
+    /// Leave out lines that are empty or only have whitespace
+    BlankLines,
+}
+
+/// How comments and strings are written in a language.
+struct Syntax {
+    line: &'static [&'static str],
+    block: &'static [(&'static str, &'static str)],
+    /// Strings that are comments when they start a line (e.g. Python docstrings).
+    docstrings: &'static [&'static str],
+    quotes: &'static [char],
+    /// Whether line comments can only start a word (e.g. `#` in shell, but not `$#`).
+    line_at_word_start: bool,
+}
+
+const C_LINE: &[&str] = &["//"];
+const C_BLOCK: &[(&str, &str)] = &[("/*", "*/")];
+
+impl Syntax {
+    fn of(language: Language) -> Self {
+        let syntax = |line, block, quotes| Self {
+            line,
+            block,
+            docstrings: &[],
+            quotes,
+            line_at_word_start: false,
+        };
+
+        match language {
+            Language::C
+            | Language::Cpp
+            | Language::Csharp
+            | Language::Java
+            | Language::Kotlin
+            | Language::Rust => syntax(C_LINE, C_BLOCK, &['"']),
+            Language::Go => syntax(C_LINE, C_BLOCK, &['"', '`']),
+            Language::Javascript | Language::Typescript => {
+                syntax(C_LINE, C_BLOCK, &['"', '\'', '`'])
+            }
+            Language::Php => syntax(&["//", "#"], C_BLOCK, &['"', '\'']),
+            Language::Python => Self {
+                docstrings: &["\"\"\"", "'''"],
+                ..syntax(&["#"], &[], &['"', '\''])
+            },
+            Language::Ruby => syntax(&["#"], &[("=begin", "=end")], &['"', '\'']),
+            Language::Shell => Self {
+                line_at_word_start: true,
+                ..syntax(&["#"], &[], &['"', '\''])
+            },
+            Language::Haskell => syntax(&["--"], &[("{-", "-}")], &['"']),
+            Language::Lua => syntax(&["--"], &[("--[[", "]]")], &['"', '\'']),
+            Language::Sql => syntax(&["--"], C_BLOCK, &['"', '\'']),
+        }
+    }
+}
+
+/// The code of every line, without its comments.
+///
+/// Strings are skipped over so that they can contain comment markers, but they are assumed to
+/// end on the same line.
+pub fn strip_comments<S: AsRef<str>>(lines: &[S], language: Language) -> Vec<String> {
+    let syntax = Syntax::of(language);
+    let mut end_of_comment: Option<&str> = None;
+
+    lines
+        .iter()
+        .map(|line| {
+            let mut code = String::new();
+            let mut rest = line.as_ref();
+            let mut quote: Option<char> = None;
+
+            while let Some(c) = rest.chars().next() {
+                if let Some(end) = end_of_comment {
+                    match rest.find(end) {
+                        Some(i) => {
+                            rest = &rest[i + end.len()..];
+                            end_of_comment = None;
+                        }
+                        None => rest = "",
+                    }
+                    continue;
+                }
+
+                if let Some(q) = quote {
+                    let escaped = c == '\\' && rest.len() > 1;
+                    let len = if escaped {
+                        1 + rest[1..].chars().next().unwrap().len_utf8()
+                    } else {
+                        c.len_utf8()
+                    };
+                    code.push_str(&rest[..len]);
+                    rest = &rest[len..];
+                    if c == q {
+                        quote = None;
+                    }
+                    continue;
+                }
+
+                let docstring = syntax
+                    .docstrings
+                    .iter()
+                    .find(|d| code.trim().is_empty() && rest.starts_with(**d))
+                    .map(|&d| (d, d));
+                let block = syntax
+                    .block
+                    .iter()
+                    .find(|(start, _)| rest.starts_with(start))
+                    .copied();
+                if let Some((start, end)) = docstring.or(block) {
+                    end_of_comment = Some(end);
+                    rest = &rest[start.len()..];
+                    continue;
+                }
+
+                let at_word_start = !syntax.line_at_word_start
+                    || code.chars().last().is_none_or(char::is_whitespace);
+                if at_word_start && syntax.line.iter().any(|start| rest.starts_with(start)) {
+                    break;
+                }
+
+                if syntax.quotes.contains(&c) {
+                    quote = Some(c);
+                }
+                code.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+
+            code
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::strip_comments;
+    use crate::lang::Language;
+
+    #[test]
+    fn test_c_comments() {
+        let lines = [
+            "/* License header",
+            " * spanning lines */",
+            "int x = 1; // one",
+            "char *s = \"// not a comment\"; /* inline */ int y;",
+            "// only a comment",
+        ];
+
+        assert_eq!(
+            strip_comments(&lines, Language::C),
+            [
+                "",
+                "",
+                "int x = 1; ",
+                "char *s = \"// not a comment\";  int y;",
+                ""
+            ]
+        );
+    }
+
+    #[test]
+    fn test_python_comments() {
+        let lines = [
+            "def f():",
+            "    \"\"\"Docstring",
+            "    that goes on\"\"\"",
+            "    s = \"\"\"not a docstring\"\"\"",
+            "    return '#' # hash",
+        ];
+
+        assert_eq!(
+            strip_comments(&lines, Language::Python),
+            [
+                "def f():",
+                "    ",
+                "",
+                "    s = \"\"\"not a docstring\"\"\"",
+                "    return '#' "
+            ]
+        );
+    }
+
+    #[test]
+    fn test_other_comments() {
+        assert_eq!(
+            strip_comments(&["echo $# # count"], Language::Shell),
+            ["echo $# "]
+        );
+        assert_eq!(
+            strip_comments(&["x = 1 --[[ a", "b ]] y = 2 -- c"], Language::Lua),
+            ["x = 1 ", " y = 2 "]
+        );
+        assert_eq!(
+            strip_comments(&["{- a -} f = 1 -- b"], Language::Haskell),
+            [" f = 1 "]
+        );
+    }
+}
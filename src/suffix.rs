@@ -0,0 +1,248 @@
+use crate::cli::Cli;
+use crate::types::FileCache;
+
+use std::collections::BTreeMap;
+
+/// Every line of every file, laid out one after the other.
+///
+/// Each line is represented by its interned id, so equal lines get equal tokens. Files are
+/// separated by tokens that are unique within the whole corpus, which stops repeated runs from
+/// spanning more than one file.
+struct Corpus {
+    tokens: Vec<usize>,
+    /// The file index of each token (`None` for separators).
+    files: Vec<Option<usize>>,
+    /// The index of the line of each token in its file.
+    lines: Vec<usize>,
+    /// Whether each token is long enough to start a block, and isn't suppressed.
+    starts_block: Vec<bool>,
+    alphabet_size: usize,
+}
+
+impl Corpus {
+    fn from_cache(args: &Cli, cache: &FileCache) -> Self {
+        let mut tokens = Vec::new();
+        let mut files = Vec::new();
+        let mut lines = Vec::new();
+        let mut starts_block = Vec::new();
+        let mut alphabet_size = cache.unique_lines;
+
+        for (file_index, file) in cache.files.iter().enumerate() {
+            for (line_index, line) in file.lines.iter().enumerate() {
+                // Suppressed lines get tokens that are unique, like separators
+                if line.suppressed {
+                    tokens.push(alphabet_size);
+                    alphabet_size += 1;
+                } else {
+                    tokens.push(line.id);
+                }
+                files.push(Some(file_index));
+                lines.push(line_index);
+                starts_block.push(!line.suppressed && line.text.len() >= args.line_threshold);
+            }
+
+            // Separators go after every line id so that they never compare equal to anything
+            tokens.push(alphabet_size);
+            files.push(None);
+            lines.push(0);
+            starts_block.push(false);
+            alphabet_size += 1;
+        }
+
+        Self {
+            tokens,
+            files,
+            lines,
+            starts_block,
+            alphabet_size,
+        }
+    }
+}
+
+/// Stable counting sort of `items` by `key`, where every key is less than `key_size`.
+fn counting_sort(items: &[usize], key: &[usize], key_size: usize) -> Vec<usize> {
+    let mut counts = vec![0usize; key_size + 1];
+    for &i in items {
+        counts[key[i] + 1] += 1;
+    }
+
+    for k in 1..counts.len() {
+        counts[k] += counts[k - 1];
+    }
+
+    let mut sorted = vec![0; items.len()];
+    for &i in items {
+        sorted[counts[key[i]]] = i;
+        counts[key[i]] += 1;
+    }
+
+    sorted
+}
+
+/// Build the suffix array of `s` by prefix doubling.
+///
+/// Every round sorts the suffixes by their first `2k` tokens using the ranks of their first `k`
+/// tokens, with 2 passes of counting sort. There are at most log(n) rounds, so this runs in
+/// O(n log n).
+pub fn suffix_array(s: &[usize], alphabet_size: usize) -> Vec<usize> {
+    let n = s.len();
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let all: Vec<usize> = (0..n).collect();
+    let mut sa = counting_sort(&all, s, alphabet_size);
+    let mut rank = s.to_vec();
+    let mut next_rank = vec![0; n];
+    let mut k = 1;
+
+    loop {
+        let second = |i: usize, rank: &[usize]| if i + k < n { Some(rank[i + k]) } else { None };
+
+        // Suffixes sorted by their second half; those without a second half come first
+        let mut by_second = Vec::with_capacity(n);
+        by_second.extend(n.saturating_sub(k)..n);
+        by_second.extend(sa.iter().filter(|&&i| i >= k).map(|&i| i - k));
+
+        sa = counting_sort(&by_second, &rank, std::cmp::max(alphabet_size, n));
+
+        next_rank[sa[0]] = 0;
+        for w in 1..n {
+            let (a, b) = (sa[w - 1], sa[w]);
+            let is_same = rank[a] == rank[b] && second(a, &rank) == second(b, &rank);
+            next_rank[b] = next_rank[a] + usize::from(!is_same);
+        }
+        std::mem::swap(&mut rank, &mut next_rank);
+
+        if rank[sa[n - 1]] == n - 1 {
+            return sa;
+        }
+
+        k *= 2;
+    }
+}
+
+/// Kasai's algorithm for the longest common prefix of neighbouring suffixes.
+///
+/// `lcp[i]` is the length of the common prefix of `sa[i - 1]` and `sa[i]` (`lcp[0]` is 0).
+pub fn lcp_array(s: &[usize], sa: &[usize]) -> Vec<usize> {
+    let n = s.len();
+    let mut rank = vec![0; n];
+    for (i, &p) in sa.iter().enumerate() {
+        rank[p] = i;
+    }
+
+    let mut lcp = vec![0; n];
+    let mut h = 0;
+    for p in 0..n {
+        if rank[p] == 0 {
+            h = 0;
+            continue;
+        }
+
+        let q = sa[rank[p] - 1];
+        while p + h < n && q + h < n && s[p + h] == s[q + h] {
+            h += 1;
+        }
+
+        lcp[rank[p]] = h;
+        h = h.saturating_sub(1);
+    }
+
+    lcp
+}
+
+/// The lines of the first file of a pair that can start a block shared with the second file.
+pub type FilePairs = BTreeMap<(usize, usize), Vec<usize>>;
+
+/// Find the pairs of files that share a repeated block, across every file at once.
+///
+/// All files are laid out into a single stream of tokens. Blocks of at least `block_threshold`
+/// lines that appear more than once are runs of neighbouring suffixes in the suffix array whose
+/// common prefixes are at least that long, so every file with a block in the same run is paired
+/// up (and a file with itself if the block appears twice in it), along with the lines of the
+/// first file that the run starts on.
+///
+/// Only these lines can start matches, so comparing them with `get_matches_from_lines` gives the
+/// same groups as comparing every line of every pair of files. A block that is in every file
+/// still pairs up every file, so the worst case stays O(n²) pairs.
+pub fn file_pairs(args: &Cli, cache: &FileCache) -> FilePairs {
+    let corpus = Corpus::from_cache(args, cache);
+    let sa = suffix_array(&corpus.tokens, corpus.alphabet_size);
+    let lcp = lcp_array(&corpus.tokens, &sa);
+    let n = sa.len();
+
+    let mut pairs = FilePairs::new();
+    let mut lb = 0;
+    while lb < n {
+        let mut rb = lb + 1;
+        while rb < n && lcp[rb] >= args.block_threshold {
+            rb += 1;
+        }
+
+        // Lines that share an id can differ in length with `--normalize`, so only the line in the
+        // first file of a pair decides whether it is long enough (see `get_matches_from_lines`)
+        if rb - lb > 1 && sa[lb..rb].iter().any(|&i| corpus.starts_block[i]) {
+            let mut lines_by_file: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+            for &i in &sa[lb..rb] {
+                if let Some(file) = corpus.files[i] {
+                    lines_by_file.entry(file).or_default().push(corpus.lines[i]);
+                }
+            }
+
+            let files: Vec<usize> = lines_by_file.keys().copied().collect();
+            for (k, (&file, lines)) in lines_by_file.iter().enumerate() {
+                // A file is only paired with itself if the block appears twice in it
+                let partners = files[k..]
+                    .iter()
+                    .filter(|&&other| other != file || lines.len() > 1);
+                for &other in partners {
+                    pairs.entry((file, other)).or_default().extend(lines);
+                }
+            }
+        }
+
+        lb = rb;
+    }
+
+    for lines in pairs.values_mut() {
+        lines.sort_unstable();
+        lines.dedup();
+    }
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lcp_array, suffix_array};
+
+    fn naive_suffix_array(s: &[usize]) -> Vec<usize> {
+        let mut sa: Vec<usize> = (0..s.len()).collect();
+        sa.sort_by_key(|&i| &s[i..]);
+        sa
+    }
+
+    #[test]
+    fn test_suffix_array() {
+        // "banana" and "mississippi"
+        let banana = [1, 0, 2, 0, 2, 0];
+        let mississippi = [1, 0, 3, 3, 0, 3, 3, 0, 2, 2, 0];
+
+        assert_eq!(suffix_array(&banana, 3), vec![5, 3, 1, 0, 4, 2]);
+        assert_eq!(
+            suffix_array(&mississippi, 4),
+            naive_suffix_array(&mississippi)
+        );
+        assert_eq!(suffix_array(&[0, 0, 0, 0], 1), vec![3, 2, 1, 0]);
+        assert_eq!(suffix_array(&[], 0), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_lcp_array() {
+        let banana = [1, 0, 2, 0, 2, 0];
+        let sa = suffix_array(&banana, 3);
+
+        assert_eq!(lcp_array(&banana, &sa), vec![0, 1, 3, 0, 0, 2]);
+    }
+}
@@ -0,0 +1,118 @@
+/// Comments that mark duplication as intentional.
+///
+/// Markers are found anywhere in a line, so they work with any comment syntax (e.g.
+/// `// superdiff:ignore-start` or `# superdiff:ignore-next 3`).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Marker {
+    /// Suppress this line and every line up to the next `superdiff:ignore-end`.
+    Start,
+    End,
+    /// Suppress this line and the next `n` lines (1 if `n` is left out).
+    Next(usize),
+}
+
+const START: &str = "superdiff:ignore-start";
+const END: &str = "superdiff:ignore-end";
+const NEXT: &str = "superdiff:ignore-next";
+
+impl Marker {
+    pub fn from_line(line: &str) -> Option<Self> {
+        if line.contains(START) {
+            return Some(Self::Start);
+        }
+        if line.contains(END) {
+            return Some(Self::End);
+        }
+
+        let (_, rest) = line.split_once(NEXT)?;
+        let count = rest
+            .split_whitespace()
+            .next()
+            .and_then(|word| word.parse().ok())
+            .unwrap_or(1);
+        Some(Self::Next(count))
+    }
+}
+
+/// Whether each line is suppressed by a marker.
+///
+/// Marker lines are suppressed as well. A `superdiff:ignore-start` without an end suppresses the
+/// rest of the file, and an end without a start is ignored.
+pub fn suppressed_lines<S: AsRef<str>>(lines: &[S]) -> Vec<bool> {
+    let mut suppressed = Vec::with_capacity(lines.len());
+    let mut in_region = false;
+    let mut next_lines = 0;
+
+    for line in lines {
+        match Marker::from_line(line.as_ref()) {
+            Some(Marker::Start) => {
+                in_region = true;
+                suppressed.push(true);
+            }
+            Some(Marker::End) => {
+                suppressed.push(in_region);
+                in_region = false;
+            }
+            Some(Marker::Next(n)) => {
+                next_lines = n;
+                suppressed.push(true);
+            }
+            None if next_lines > 0 => {
+                next_lines -= 1;
+                suppressed.push(true);
+            }
+            None => suppressed.push(in_region),
+        }
+    }
+
+    suppressed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{suppressed_lines, Marker};
+
+    #[test]
+    fn test_markers() {
+        assert_eq!(
+            Marker::from_line("// superdiff:ignore-start (generated)"),
+            Some(Marker::Start)
+        );
+        assert_eq!(
+            Marker::from_line("<!-- superdiff:ignore-end -->"),
+            Some(Marker::End)
+        );
+        assert_eq!(
+            Marker::from_line("# superdiff:ignore-next 3"),
+            Some(Marker::Next(3))
+        );
+        assert_eq!(
+            Marker::from_line("-- superdiff:ignore-next"),
+            Some(Marker::Next(1))
+        );
+        assert_eq!(Marker::from_line("x = 1 # superdiff"), None);
+    }
+
+    #[test]
+    fn test_suppressed_lines() {
+        let lines = [
+            "a",
+            "// superdiff:ignore-next 2",
+            "b",
+            "c",
+            "d",
+            "/* superdiff:ignore-start */",
+            "e",
+            "/* superdiff:ignore-end */",
+            "f",
+            "/* superdiff:ignore-end */",
+            "# superdiff:ignore-start",
+            "g",
+        ];
+
+        assert_eq!(
+            suppressed_lines(&lines),
+            [false, true, true, true, false, true, true, true, false, false, true, true]
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use tree_sitter::{Node, Parser};
+use tree_sitter_language::LanguageFn;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc;
+
+use crate::cli::Cli;
+use crate::lang::Language;
+use crate::math::{fnv1a, FNV_OFFSET_BASIS};
+use crate::normalize::Normalization;
+use crate::types::{CompFile, FileCache, Match};
+
+const IDENTIFIER_PLACEHOLDER: &[u8] = b"$id";
+const LITERAL_PLACEHOLDER: &[u8] = b"$lit";
+
+// Generated parsers that are vendored in `vendor/` and compiled by `build.rs`
+extern "C" {
+    fn tree_sitter_c() -> *const ();
+    fn tree_sitter_go() -> *const ();
+    fn tree_sitter_java() -> *const ();
+    fn tree_sitter_javascript() -> *const ();
+    fn tree_sitter_python() -> *const ();
+    fn tree_sitter_rust() -> *const ();
+}
+
+/// The grammar of a language, if it is supported.
+fn grammar(language: Language) -> Option<tree_sitter::Language> {
+    let language_fn = match language {
+        Language::C => tree_sitter_c,
+        Language::Go => tree_sitter_go,
+        Language::Java => tree_sitter_java,
+        Language::Javascript => tree_sitter_javascript,
+        Language::Python => tree_sitter_python,
+        Language::Rust => tree_sitter_rust,
+        _ => return None,
+    };
+
+    // SAFETY: these are the language functions of parsers generated by the tree-sitter CLI
+    Some(unsafe { LanguageFn::from_raw(language_fn) }.into())
+}
+
+/// Kinds of nodes that are reported on their own: functions, methods, classes and blocks.
+fn unit_kinds(language: Language) -> &'static [&'static str] {
+    match language {
+        Language::C => &[
+            "function_definition",
+            "struct_specifier",
+            "compound_statement",
+        ],
+        Language::Go => &[
+            "function_declaration",
+            "method_declaration",
+            "func_literal",
+            "block",
+        ],
+        Language::Java => &[
+            "method_declaration",
+            "constructor_declaration",
+            "class_declaration",
+            "interface_declaration",
+            "block",
+        ],
+        Language::Javascript => &[
+            "function_declaration",
+            "function_expression",
+            "arrow_function",
+            "method_definition",
+            "class_declaration",
+            "statement_block",
+        ],
+        Language::Python => &["function_definition", "class_definition", "block"],
+        Language::Rust => &[
+            "function_item",
+            "impl_item",
+            "trait_item",
+            "mod_item",
+            "block",
+        ],
+        _ => &[],
+    }
+}
+
+fn is_identifier(node: &Node) -> bool {
+    node.kind().ends_with("identifier")
+}
+
+fn is_literal(node: &Node) -> bool {
+    let kind = node.kind();
+    kind.contains("literal")
+        || kind.contains("string")
+        || matches!(kind, "integer" | "float" | "number" | "true" | "false")
+}
+
+/// A function, method, class or block.
+struct Unit {
+    file: usize,
+    /// First and last row, starting at 0.
+    rows: (usize, usize),
+    hash: u64,
+    /// The closest unit that contains this one.
+    parent: Option<usize>,
+}
+
+/// Hashes the subtrees of a file, and collects its units.
+struct Hasher<'a> {
+    args: &'a Cli,
+    source: &'a [u8],
+    file: usize,
+    kinds: &'static [&'static str],
+    units: &'a mut Vec<Unit>,
+}
+
+impl Hasher<'_> {
+    /// Hash the structure of a subtree, and the text of its leaves.
+    ///
+    /// Comments are left out, and identifiers and literals are replaced by placeholders with
+    /// `--normalize`.
+    fn hash(&mut self, node: Node, parent: Option<usize>) -> u64 {
+        let mut hash = fnv1a(FNV_OFFSET_BASIS, node.kind().as_bytes());
+
+        if self.args.normalize.contains(&Normalization::Literals) && is_literal(&node) {
+            return fnv1a(hash, LITERAL_PLACEHOLDER);
+        }
+        if node.child_count() == 0 {
+            let text = if self.args.normalize.contains(&Normalization::Identifiers)
+                && is_identifier(&node)
+            {
+                IDENTIFIER_PLACEHOLDER
+            } else {
+                &self.source[node.byte_range()]
+            };
+            return fnv1a(hash, text);
+        }
+
+        let unit = self.kinds.contains(&node.kind()).then(|| {
+            self.units.push(Unit {
+                file: self.file,
+                rows: (node.start_position().row, node.end_position().row),
+                hash: 0,
+                parent,
+            });
+            self.units.len() - 1
+        });
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor).filter(|child| !child.is_extra()) {
+            let child = self.hash(child, unit.or(parent));
+            hash = fnv1a(hash, &child.to_le_bytes());
+        }
+
+        if let Some(unit) = unit {
+            self.units[unit].hash = hash;
+        }
+        hash
+    }
+}
+
+/// Parse every file in a supported language, and collect their units.
+fn units(args: &Cli, cache: &FileCache) -> Vec<Unit> {
+    let mut units = Vec::new();
+    let mut parser = Parser::new();
+
+    for (i, file) in cache.files.iter().enumerate() {
+        let Some(language) = Language::from_path(&file.file) else {
+            continue;
+        };
+        let Some(grammar) = grammar(language) else {
+            continue;
+        };
+        parser
+            .set_language(&grammar)
+            .expect("grammars are compatible with the tree-sitter library");
+
+        let source = source_of(file);
+        let Some(tree) = parser.parse(&source, None) else {
+            continue;
+        };
+
+        Hasher {
+            args,
+            source: source.as_bytes(),
+            file: i,
+            kinds: unit_kinds(language),
+            units: &mut units,
+        }
+        .hash(tree.root_node(), None);
+    }
+
+    units
+}
+
+fn source_of(file: &CompFile) -> String {
+    file.source
+        .iter()
+        .map(|line| line.raw.as_str())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+/// Find groups of functions, methods, classes and blocks that are syntactically equal.
+///
+/// Every supported file is parsed with tree-sitter, and each unit is hashed by the shape of its
+/// subtree and the text of its leaves, so formatting and comments don't matter. With
+/// `--normalize`, units that only differ in their identifiers or literals are equal too.
+///
+/// Units that are shorter than `block_threshold` lines or that have suppressed lines are left
+/// out. A group is also left out if every unit in it is inside a unit that is duplicated, since
+/// it is already part of a larger group.
+pub fn get_matches_from_files(args: &Cli, cache: &FileCache, tx: &mpsc::Sender<(Match, Match)>) {
+    let units = units(args, cache);
+
+    let mut groups: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (i, unit) in units.iter().enumerate() {
+        let (first, last) = unit.rows;
+        let file = &cache.files[unit.file];
+        let is_suppressed = file.source[first..=last].iter().any(|line| line.suppressed);
+
+        if last - first + 1 >= args.block_threshold && !is_suppressed {
+            groups.entry(unit.hash).or_default().push(i);
+        }
+    }
+    groups.retain(|_, members| members.len() > 1);
+
+    let duplicated: HashSet<usize> = groups.values().flatten().copied().collect();
+    for members in groups.values() {
+        let is_nested = members
+            .iter()
+            .all(|&i| units[i].parent.is_some_and(|p| duplicated.contains(&p)));
+        if is_nested {
+            continue;
+        }
+
+        let to_match = |i: usize| {
+            let (first, last) = units[i].rows;
+            Match {
+                file: cache.files[units[i].file].file.clone(),
+                line: first + 1,
+                size: last - first + 1,
+                gap_lines: 0,
+            }
+        };
+        for w in members.windows(2) {
+            tx.send((to_match(w[0]), to_match(w[1]))).unwrap_or(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{grammar, Hasher, Unit};
+    use crate::cli::Cli;
+    use crate::lang::Language;
+    use crate::normalize::Normalization;
+
+    use tree_sitter::Parser;
+
+    /// The hashes of the units of some Python code.
+    fn hashes(args: &Cli, source: &str) -> Vec<u64> {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&grammar(Language::Python).unwrap())
+            .unwrap();
+        let tree = parser.parse(source, None).unwrap();
+
+        let mut units: Vec<Unit> = Vec::new();
+        Hasher {
+            args,
+            source: source.as_bytes(),
+            file: 0,
+            kinds: &["function_definition"],
+            units: &mut units,
+        }
+        .hash(tree.root_node(), None);
+
+        units.iter().map(|unit| unit.hash).collect()
+    }
+
+    #[test]
+    fn test_hash_ignores_formatting_and_comments() {
+        let args = Cli::default();
+        let h = hashes(
+            &args,
+            "def f(x):\n    return x + 1\n\ndef f(x):  # same\n    return (x+1 )\n",
+        );
+
+        assert_eq!(h.len(), 2);
+        // The parentheses are part of the syntax tree
+        assert_ne!(h[0], h[1]);
+
+        let h = hashes(
+            &args,
+            "def f(x):\n    return x + 1\n\ndef f(x):  # same\n    return x+1\n",
+        );
+        assert_eq!(h[0], h[1]);
+    }
+
+    #[test]
+    fn test_hash_with_normalization() {
+        let source = "def f(x):\n    return x + 1\n\ndef g(y):\n    return y + 2\n";
+
+        let h = hashes(&Cli::default(), source);
+        assert_ne!(h[0], h[1]);
+
+        let args = Cli {
+            normalize: vec![Normalization::Identifiers, Normalization::Literals],
+            ..Cli::default()
+        };
+        let h = hashes(&args, source);
+        assert_eq!(h[0], h[1]);
+    }
+}
@@ -1,22 +1,41 @@
 use std::thread;
-use std::sync::mpsc;
-use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
 use std::collections::HashMap;
 
+use crate::baseline::Baseline;
 use crate::cli::Cli;
-use crate::comp::{get_matches_from_2_files, comparison_lambda, update_matches};
-use crate::types::{MatchesLookup, Matches, CompFile, JsonRoot};
-use crate::printer;
+use crate::comp::{
+    comparison_lambda, get_matches_from_2_files, get_matches_from_lines, update_matches,
+};
+use crate::math::combinations;
+use crate::types::{MatchesLookup, Matches, Match, FileCache, JsonRoot};
+use crate::{printer, snap, suffix};
+
+/// A pair of files to compare, and the lines of the first file that blocks can start on (or
+/// `None` for every line).
+type FilePair = ((usize, usize), Option<Vec<usize>>);
+
+/// Called with every pair of matching blocks as soon as it is found.
+pub type PairCallback = Box<dyn FnMut(&(Match, Match))>;
 
 pub struct ThreadPool {
     where_is_match: MatchesLookup,
     matches_hash: Matches,
+    cache: Arc<FileCache>,
+    /// Matches that are left out of the results.
+    baseline: Option<Baseline>,
+    /// Every match that was found, including the ones in `baseline`.
+    found: Baseline,
+    on_pair: Option<PairCallback>,
     args: Cli,
 }
 
 impl ThreadPool {
     /// Split the pairs of files (the task) evenly among the n threads.
-    fn partition_file_combinations(&self) -> Vec<mpsc::Receiver<(PathBuf, PathBuf)>> {
+    fn partition_file_combinations(
+        &self,
+        pairs: impl Iterator<Item = FilePair>,
+    ) -> Vec<mpsc::Receiver<FilePair>> {
         let mut senders = Vec::with_capacity(self.args.worker_threads);
         let mut receivers = Vec::with_capacity(self.args.worker_threads);
 
@@ -27,36 +46,47 @@ impl ThreadPool {
         }
 
         let mut sender_index = 0;
-        for i in 0..self.args.files.len() {
-            for j in i..self.args.files.len() {
-                let two_files = (self.args.files[i].clone(), self.args.files[j].clone());
-                senders[sender_index].send(two_files).unwrap();
-
-                if sender_index == senders.len() - 1 {
-                    sender_index = 0;
-                } else {
-                    sender_index += 1;
-                }
+        for pair in pairs {
+            senders[sender_index].send(pair).unwrap();
+
+            if sender_index == senders.len() - 1 {
+                sender_index = 0;
+            } else {
+                sender_index += 1;
             }
         }
 
         receivers
     }
 
-    /// Run comparisons using the arguments from initialization.
-    pub fn run_and_get_results(&mut self) -> JsonRoot {
-        let (matches_transmitter, matches_receiver) = mpsc::channel();
+    /// Spawn the worker threads that compare pairs of files.
+    fn spawn_pairwise_workers(
+        &self,
+        matches_transmitter: mpsc::Sender<(Match, Match)>,
+        (pairs, total): (impl Iterator<Item = FilePair>, usize),
+    ) {
         let (done_transmitter, done_receiver) = mpsc::channel();
 
-        for two_file_rx in self.partition_file_combinations() {
+        for two_file_rx in self.partition_file_combinations(pairs) {
             let matches_transmitter = matches_transmitter.clone();
             let done_transmitter = done_transmitter.clone();
             let args = self.args.clone();
+            let cache = Arc::clone(&self.cache);
             thread::spawn(move || {
                 let comp = comparison_lambda(&args);
-                for (f1, f2) in two_file_rx {
-                    if let Some(two_files) = CompFile::from_files(&f1, &f2) {
-                        get_matches_from_2_files(&args, &matches_transmitter, &comp, two_files);
+                for ((i, j), starts) in two_file_rx {
+                    let two_files = (cache.files[i].clone(), cache.files[j].clone());
+                    match starts {
+                        Some(starts) => get_matches_from_lines(
+                            &args,
+                            &matches_transmitter,
+                            &comp,
+                            two_files,
+                            &starts,
+                        ),
+                        None => {
+                            get_matches_from_2_files(&args, &matches_transmitter, &comp, two_files)
+                        }
                     }
 
                     done_transmitter.send(true).unwrap_or(());
@@ -64,17 +94,93 @@ impl ThreadPool {
             });
         }
 
-        // We have to drop this these otherwise rx won't know when to quit and will keep waiting
-        drop(matches_transmitter);
+        // We have to drop this otherwise rx won't know when to quit and will keep waiting
         drop(done_transmitter);
 
-        printer::spawn_processing_text(&self.args, done_receiver);
+        printer::spawn_processing_text(&self.args, total, done_receiver);
+    }
+
+    /// Spawn a single thread that looks for syntactically equal units across all files.
+    #[cfg(feature = "syntax")]
+    fn spawn_syntax_engine(&self, matches_transmitter: mpsc::Sender<(Match, Match)>) {
+        let args = self.args.clone();
+        let cache = Arc::clone(&self.cache);
+        thread::spawn(move || {
+            crate::syntax::get_matches_from_files(&args, &cache, &matches_transmitter);
+        });
+    }
+
+    /// Run comparisons using the arguments from initialization.
+    pub fn run_and_get_results(&mut self) -> JsonRoot {
+        self.cache = Arc::new(FileCache::from_args(&self.args));
+        let (matches_transmitter, matches_receiver) = mpsc::channel();
+
+        if self.args.uses_syntax() {
+            #[cfg(feature = "syntax")]
+            self.spawn_syntax_engine(matches_transmitter);
+            #[cfg(not(feature = "syntax"))]
+            unreachable!("`Cli::load_from` rejects --engine syntax without the `syntax` feature");
+        } else if self.args.uses_suffix_array() {
+            // Only the lines that start a repeated block, in the pairs of files that share one
+            let pairs = suffix::file_pairs(&self.args, &self.cache);
+            let total = pairs.len();
+            let pairs = pairs.into_iter().map(|(pair, starts)| (pair, Some(starts)));
+            self.spawn_pairwise_workers(matches_transmitter, (pairs, total));
+        } else {
+            let n = self.cache.files.len();
+            let pairs = (0..n).flat_map(|i| (i..n).map(move |j| ((i, j), None)));
+            self.spawn_pairwise_workers(matches_transmitter, (pairs, combinations(n, 2) + n));
+        }
 
         for matches in matches_receiver {
+            if let Some(on_pair) = &mut self.on_pair {
+                on_pair(&matches);
+            }
             update_matches(matches, (&mut self.where_is_match, &mut self.matches_hash));
         }
 
-        JsonRoot::from(&self.matches_hash)
+        if self.args.snap {
+            self.matches_hash = snap::snap_matches(&self.args, &self.cache, &self.matches_hash);
+        }
+
+        let mut root = JsonRoot::from(&self.matches_hash);
+        root.errors = self.cache.errors.clone();
+        root.suppressed_lines = self.cache.suppressed_lines();
+        self.cache.add_fingerprints(&mut root);
+
+        self.found = Baseline::from(&root);
+        if let Some(baseline) = &self.baseline {
+            baseline.filter(&mut root);
+        }
+
+        root.sort(self.args.sort);
+        self.cache.add_metrics(&mut root);
+
+        root
+    }
+
+    /// Leave the matches in the baseline out of the results.
+    pub fn with_baseline(mut self, baseline: Baseline) -> Self {
+        self.baseline = Some(baseline);
+        self
+    }
+
+    /// Call `on_pair` with every pair of matching blocks as soon as it is found, while
+    /// `run_and_get_results` is still searching.
+    pub fn with_pair_callback(mut self, on_pair: impl FnMut(&(Match, Match)) + 'static) -> Self {
+        self.on_pair = Some(Box::new(on_pair));
+        self
+    }
+
+    /// A baseline of every match that was found by `run_and_get_results`, including the ones
+    /// that were left out because of `with_baseline`.
+    pub fn found(&self) -> &Baseline {
+        &self.found
+    }
+
+    /// The files that were read by `run_and_get_results`.
+    pub fn cache(&self) -> Arc<FileCache> {
+        Arc::clone(&self.cache)
     }
 }
 
@@ -83,6 +189,10 @@ impl From<Cli> for ThreadPool {
         Self {
             where_is_match: MatchesLookup(HashMap::new()),
             matches_hash: Matches(HashMap::new()),
+            cache: Arc::new(FileCache::default()),
+            baseline: None,
+            found: Baseline::default(),
+            on_pair: None,
             args: item,
         }
     }
@@ -1,17 +1,70 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
+use crate::cli::{Cli, SortOrder};
+use crate::encoding::{decode, split_lines, Encoding};
+use crate::lang::Language;
+use crate::math::{fnv1a, FNV_OFFSET_BASIS};
+use crate::normalize::normalize;
+use crate::strip::{strip_comments, Strip};
+use crate::suppress::suppressed_lines;
+
+/// A single line of a file.
+#[derive(Clone, Debug)]
+pub struct Line {
+    /// Equal lines share the same id across all files.
+    pub id: usize,
+    /// The line without leading or trailing whitespace, or comments (see `--strip`).
+    pub text: String,
+    /// The line as it is in the file, without the line ending.
+    pub raw: String,
+    /// The text with some tokens replaced by placeholders (see `--normalize`).
+    ///
+    /// Is compared instead of the text if set.
+    pub normalized: Option<String>,
+    /// Whether the line is suppressed by a `superdiff:ignore` comment.
+    ///
+    /// Suppressed lines never match any other line.
+    pub suppressed: bool,
+    /// The number of the line in the file, starting at 1.
+    pub number: usize,
+}
+
+/// Positions of the lines in a file, keyed by line id.
+///
+/// The positions of each id are sorted in ascending order.
+pub type LineIndex = HashMap<usize, Vec<usize>>;
+
 /// A structure to easily move parameters from one place to another.
+///
+/// The contents of the file are shared, so this is cheap to clone.
 #[derive(Clone, Debug)]
 pub struct CompFile {
     pub file: PathBuf,
-    pub lines: Vec<String>,
+    /// The lines that are compared, which are all of them unless some are stripped (see
+    /// `--strip`).
+    pub lines: Arc<Vec<Line>>,
+    /// Every line of the file, for showing blocks.
+    pub source: Arc<Vec<Line>>,
+    pub index: Arc<LineIndex>,
     pub start: usize,
 }
 
+/// Every file that was read, with their lines interned.
+///
+/// Each file is only read once, no matter how many comparisons it is involved in.
+#[derive(Default)]
+pub struct FileCache {
+    pub files: Vec<CompFile>,
+    /// Number of distinct lines across all files (i.e. one more than the largest line id).
+    pub unique_lines: usize,
+    pub errors: Vec<JsonFileError>,
+}
+
 /// A matching block.
 ///
 /// Points to a single block of lines in some file.
@@ -20,6 +73,8 @@ pub struct Match {
     pub file: PathBuf,
     pub line: usize,
     pub size: usize,
+    /// Lines inside the block that were skipped over in this file (see `--max-gap`).
+    pub gap_lines: usize,
 }
 
 /// A bunch of Matches.
@@ -33,28 +88,92 @@ pub struct Matches(pub HashMap<Match, Vec<Match>>);
 /// Used to check which key some match belongs to, in order to insert into `Matches`.
 pub struct MatchesLookup(pub HashMap<Match, Match>);
 
-#[derive(Serialize, Clone, Deserialize, PartialEq, Eq, Debug)]
+#[derive(Serialize, Clone, Deserialize, PartialEq, Debug)]
 pub struct JsonFileInfo {
     pub count_blocks: usize,
+    /// Only set for the files of `JsonRoot`, not the files of each match.
+    #[serde(flatten)]
+    pub metrics: Option<JsonMetrics>,
+}
+
+/// How many lines are part of any block.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct JsonMetrics {
+    pub total_lines: usize,
+    /// Lines that are part of at least one block, where overlapping blocks are counted once.
+    pub duplicated_lines: usize,
+    /// `duplicated_lines / total_lines`, or 0 if there are no lines.
+    pub duplication_ratio: f64,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct JsonBlockInfo {
     pub starting_line: usize,
     pub block_length: usize,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub gap_lines: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
 }
 
-#[derive(Serialize, Deserialize, Eq, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct JsonMatch {
-    pub files: HashMap<PathBuf, JsonFileInfo>,
-    pub blocks: HashMap<PathBuf, Vec<JsonBlockInfo>>,
+    pub files: BTreeMap<PathBuf, JsonFileInfo>,
+    /// The blocks in each file, sorted by line.
+    pub blocks: BTreeMap<PathBuf, Vec<JsonBlockInfo>>,
+    /// Identifies the group across runs, as long as the contents of its blocks don't change.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub fingerprint: String,
 }
 
-#[derive(Serialize, Deserialize, Eq, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct JsonRoot {
     pub version: String,
-    pub files: HashMap<PathBuf, JsonFileInfo>,
+    pub files: BTreeMap<PathBuf, JsonFileInfo>,
+    /// Sorted by `JsonRoot::sort`.
     pub matches: Vec<JsonMatch>,
+    /// Metrics of every directory that contains a file, including the files in subdirectories.
+    #[serde(default)]
+    pub directories: BTreeMap<PathBuf, JsonMetrics>,
+    /// Metrics of every file that was read.
+    #[serde(default)]
+    pub summary: JsonMetrics,
+    /// Number of matches that were left out because they are in the baseline (see `--baseline`).
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub known_matches: usize,
+    /// Number of lines that were suppressed by `superdiff:ignore` comments.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub suppressed_lines: usize,
+    /// Files that could not be read, and were left out of every comparison.
+    #[serde(default)]
+    pub errors: Vec<JsonFileError>,
+}
+
+/// A block of one of the pairs that are streamed by `ReportingMode::Jsonl`.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct JsonPairBlock {
+    pub file: PathBuf,
+    #[serde(flatten)]
+    pub info: JsonBlockInfo,
+}
+
+/// A line of `ReportingMode::Jsonl`.
+///
+/// Every pair of matching blocks is printed as soon as it is found, and the merged matches are
+/// printed at the end.
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JsonLine<'a> {
+    Pair { blocks: [JsonPairBlock; 2] },
+    Result(&'a JsonRoot),
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct JsonFileError {
+    pub path: PathBuf,
+    pub error: String,
 }
 
 impl From<&Match> for JsonBlockInfo {
@@ -62,10 +181,32 @@ impl From<&Match> for JsonBlockInfo {
         Self {
             starting_line: m.line,
             block_length: m.size,
+            gap_lines: m.gap_lines,
         }
     }
 }
 
+impl From<&Match> for JsonPairBlock {
+    fn from(m: &Match) -> Self {
+        Self {
+            file: m.file.clone(),
+            info: JsonBlockInfo::from(m),
+        }
+    }
+}
+
+impl JsonLine<'_> {
+    pub fn from_pair((a, b): &(Match, Match)) -> Self {
+        Self::Pair {
+            blocks: [JsonPairBlock::from(a), JsonPairBlock::from(b)],
+        }
+    }
+
+    pub fn json(&self) -> String {
+        serde_json::to_string(&self).unwrap_or("{}".to_owned())
+    }
+}
+
 impl PartialEq for JsonMatch {
     fn eq(&self, other: &Self) -> bool {
         if self.files != other.files || self.blocks.len() != other.blocks.len() {
@@ -93,9 +234,15 @@ impl PartialEq for JsonMatch {
 
 impl From<(&Match, &Vec<Match>)> for JsonMatch {
     fn from((initial_match, other_matches): (&Match, &Vec<Match>)) -> Self {
-        let mut blocks = HashMap::new();
-        let mut files = HashMap::new();
-        files.insert(initial_match.file.clone(), JsonFileInfo { count_blocks: 1 });
+        let mut blocks = BTreeMap::new();
+        let mut files = BTreeMap::new();
+        files.insert(
+            initial_match.file.clone(),
+            JsonFileInfo {
+                count_blocks: 1,
+                metrics: None,
+            },
+        );
         blocks.insert(
             initial_match.file.clone(),
             vec![JsonBlockInfo::from(initial_match)],
@@ -106,15 +253,60 @@ impl From<(&Match, &Vec<Match>)> for JsonMatch {
             files
                 .entry(f.clone())
                 .and_modify(|info| info.count_blocks += 1)
-                .or_insert(JsonFileInfo { count_blocks: 1 });
+                .or_insert(JsonFileInfo {
+                    count_blocks: 1,
+                    metrics: None,
+                });
             blocks
                 .entry(f)
                 .and_modify(|v| v.push(JsonBlockInfo::from(m)))
                 .or_insert(vec![JsonBlockInfo::from(m)]);
         }
 
-        Self { files, blocks }
+        for infos in blocks.values_mut() {
+            infos.sort_by_key(|info| (info.starting_line, info.block_length));
+        }
+
+        Self {
+            files,
+            blocks,
+            fingerprint: String::new(),
+        }
+    }
+}
+
+/// Describe the sizes of blocks in a file.
+///
+/// Blocks without gaps all have the same size, so we only need to print it once.
+fn format_sizes(infos: &[JsonBlockInfo]) -> String {
+    if infos.iter().all(|info| info.gap_lines == 0) {
+        return format!("Size: {}", infos[0].block_length);
     }
+
+    format!(
+        "Sizes: {:?}\nGap lines: {:?}",
+        infos
+            .iter()
+            .map(|info| info.block_length)
+            .collect::<Vec<usize>>(),
+        infos
+            .iter()
+            .map(|info| info.gap_lines)
+            .collect::<Vec<usize>>(),
+    )
+}
+
+/// Describe the blocks of a match in a file.
+pub fn format_file(filename: &Path, infos: &[JsonBlockInfo]) -> String {
+    format!(
+        "File: {}\nLines: {:?}\n{}",
+        filename.display(),
+        infos
+            .iter()
+            .map(|info| info.starting_line)
+            .collect::<Vec<usize>>(),
+        format_sizes(infos),
+    )
 }
 
 impl fmt::Display for JsonMatch {
@@ -124,15 +316,7 @@ impl fmt::Display for JsonMatch {
             "=== MATCH ===\n{}\n",
             self.blocks
                 .iter()
-                .map(|(filename, infos)| format!(
-                    "File: {}\nLines: {:?}\nSize: {}",
-                    filename.display(),
-                    infos
-                        .iter()
-                        .map(|info| info.starting_line)
-                        .collect::<Vec<usize>>(),
-                    infos[0].block_length,
-                ))
+                .map(|(filename, infos)| format_file(filename, infos))
                 .collect::<Vec<String>>()
                 .join("\n---\n")
         )
@@ -143,31 +327,168 @@ impl From<&Matches> for JsonRoot {
     fn from(m: &Matches) -> Self {
         let version = clap::crate_version!().to_owned();
         let matches: Vec<JsonMatch> = m.0.iter().map(JsonMatch::from).collect();
-        let jm_files = matches.iter().map(|jm| jm.files.clone());
-        let mut files: HashMap<PathBuf, JsonFileInfo> = HashMap::new();
-
-        for jmf in jm_files {
-            for (filename, info) in jmf {
-                files
-                    .entry(filename)
-                    .and_modify(|v| v.count_blocks += info.count_blocks)
-                    .or_insert(info);
-            }
-        }
 
         Self {
             version,
-            files,
+            files: count_blocks_per_file(&matches),
             matches,
+            directories: BTreeMap::new(),
+            summary: JsonMetrics::default(),
+            known_matches: 0,
+            suppressed_lines: 0,
+            errors: Vec::new(),
         }
     }
 }
 
+/// Add up the blocks in each file over all matches.
+fn count_blocks_per_file(matches: &[JsonMatch]) -> BTreeMap<PathBuf, JsonFileInfo> {
+    let jm_files = matches.iter().map(|jm| jm.files.clone());
+    let mut files: BTreeMap<PathBuf, JsonFileInfo> = BTreeMap::new();
+
+    for jmf in jm_files {
+        for (filename, info) in jmf {
+            files
+                .entry(filename)
+                .and_modify(|v| v.count_blocks += info.count_blocks)
+                .or_insert(info);
+        }
+    }
+
+    files
+}
+
+/// A path with forward slashes and without any leading `./`, so that it is the same everywhere.
+pub fn portable_path(path: &Path) -> String {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    path.to_string_lossy().replace('\\', "/")
+}
+
+impl JsonMetrics {
+    pub fn new(total_lines: usize, duplicated_lines: usize) -> Self {
+        Self {
+            total_lines,
+            duplicated_lines,
+            duplication_ratio: if total_lines == 0 {
+                0.0
+            } else {
+                duplicated_lines as f64 / total_lines as f64
+            },
+        }
+    }
+
+    /// Combine the metrics of two sets of lines that don't overlap.
+    pub fn add(&self, other: &Self) -> Self {
+        Self::new(
+            self.total_lines + other.total_lines,
+            self.duplicated_lines + other.duplicated_lines,
+        )
+    }
+}
+
+impl JsonMatch {
+    /// Every block, sorted by path and then by line.
+    ///
+    /// Also gives the position of each block among the blocks of the same file.
+    pub fn sorted_blocks(&self) -> Vec<(&Path, usize, &JsonBlockInfo)> {
+        self.blocks
+            .iter()
+            .flat_map(|(path, infos)| {
+                infos
+                    .iter()
+                    .enumerate()
+                    .map(move |(nth, info)| (path.as_path(), nth, info))
+            })
+            .collect()
+    }
+
+    /// The path, line and length of every block, in order.
+    fn locations(&self) -> Vec<(&Path, usize, usize)> {
+        self.sorted_blocks()
+            .into_iter()
+            .map(|(path, _, info)| (path, info.starting_line, info.block_length))
+            .collect()
+    }
+
+    /// The number of blocks.
+    pub fn count_blocks(&self) -> usize {
+        self.blocks.values().map(Vec::len).sum()
+    }
+
+    /// The number of lines in all blocks.
+    pub fn total_lines(&self) -> usize {
+        self.blocks
+            .values()
+            .flatten()
+            .map(|info| info.block_length)
+            .sum()
+    }
+
+    /// Fingerprint of a single block: the fingerprint of the match, plus where the block is.
+    ///
+    /// Blocks are identified by their file and their position among the blocks of that file
+    /// (see `sorted_blocks`) rather than their line numbers, so that the fingerprint survives code
+    /// being added above them.
+    pub fn block_fingerprint(&self, path: &Path, nth: usize) -> String {
+        let hash = [
+            self.fingerprint.as_bytes(),
+            portable_path(path).as_bytes(),
+            &nth.to_le_bytes(),
+        ]
+        .iter()
+        .fold(FNV_OFFSET_BASIS, |hash, bytes| fnv1a(hash, bytes));
+
+        format!("{hash:016x}")
+    }
+}
+
 impl JsonRoot {
     pub fn unique_matches(&self) -> usize {
         self.matches.len()
     }
 
+    /// Only keep the matches that satisfy the predicate.
+    ///
+    /// The number of blocks in each file is counted again, so this should be done before adding
+    /// the metrics.
+    pub fn retain_matches(&mut self, f: impl FnMut(&JsonMatch) -> bool) {
+        self.matches.retain(f);
+        self.files = count_blocks_per_file(&self.matches);
+    }
+
+    /// Put the matches in a stable order.
+    ///
+    /// Ties are always broken by the locations of every block, so the order is the same on every
+    /// run.
+    pub fn sort(&mut self, order: SortOrder) {
+        match order {
+            SortOrder::Location => self
+                .matches
+                .sort_by(|a, b| a.locations().cmp(&b.locations())),
+            SortOrder::Size => self.matches.sort_by(|a, b| {
+                b.count_blocks()
+                    .cmp(&a.count_blocks())
+                    .then_with(|| b.total_lines().cmp(&a.total_lines()))
+                    .then_with(|| a.locations().cmp(&b.locations()))
+            }),
+        }
+    }
+
+    /// The numbers of the lines of each file that are part of any block.
+    pub fn duplicated_lines(&self) -> HashMap<&Path, HashSet<usize>> {
+        let mut lines: HashMap<&Path, HashSet<usize>> = HashMap::new();
+        for jm in &self.matches {
+            for (path, infos) in &jm.blocks {
+                let lines = lines.entry(path.as_path()).or_default();
+                for info in infos {
+                    lines.extend(info.starting_line..info.starting_line + info.block_length);
+                }
+            }
+        }
+
+        lines
+    }
+
     pub fn json(&self) -> String {
         serde_json::to_string(&self).unwrap_or("{}".to_owned())
     }
@@ -189,6 +510,44 @@ impl PartialEq for JsonRoot {
     }
 }
 
+impl JsonRoot {
+    /// A table of the metrics of every file and directory, and of the whole run.
+    pub fn summary_table(&self) -> String {
+        let mut rows: Vec<(String, JsonMetrics)> = self
+            .files
+            .iter()
+            .map(|(path, info)| (path.display().to_string(), info.metrics.unwrap_or_default()))
+            .collect();
+        rows.extend(
+            self.directories
+                .iter()
+                .map(|(path, metrics)| (format!("{}/", path.display()), *metrics)),
+        );
+        rows.push(("Total".to_owned(), self.summary));
+
+        let width = rows
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut table = format!(
+            "{:<width$}  {:>8}  {:>10}  {:>11}",
+            "Path", "Lines", "Duplicated", "Duplication"
+        );
+        for (name, metrics) in rows {
+            table.push_str(&format!(
+                "\n{:<width$}  {:>8}  {:>10}  {:>10.1}%",
+                name,
+                metrics.total_lines,
+                metrics.duplicated_lines,
+                metrics.duplication_ratio * 100.0
+            ));
+        }
+
+        table
+    }
+}
+
 impl fmt::Display for JsonRoot {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -203,53 +562,312 @@ impl fmt::Display for JsonRoot {
     }
 }
 
-pub type ComparisonFn = Box<dyn Fn(&String, &String) -> bool>;
-pub type FileCache = HashMap<PathBuf, Vec<String>>;
+pub type ComparisonFn = Box<dyn Fn(&Line, &Line) -> bool>;
 
 impl Match {
-    pub fn from_compfiles(f1: &CompFile, f2: &CompFile, block_length: usize) -> (Self, Self) {
+    pub fn from_compfiles(
+        f1: &CompFile,
+        f2: &CompFile,
+        (size1, size2): (usize, usize),
+        (gap_lines1, gap_lines2): (usize, usize),
+    ) -> (Self, Self) {
+        let (line1, size1) = f1.span(f1.start, size1);
+        let (line2, size2) = f2.span(f2.start, size2);
+
         (
             Self {
                 file: f1.file.clone(),
-                line: f1.start + 1,
-                size: block_length,
+                line: line1,
+                size: size1,
+                gap_lines: gap_lines1,
             },
             Self {
                 file: f2.file.clone(),
-                line: f2.start + 1,
-                size: block_length,
+                line: line2,
+                size: size2,
+                gap_lines: gap_lines2,
             },
         )
     }
 }
 
-fn get_lines_from_file(file: &PathBuf) -> std::io::Result<Vec<String>> {
-    Ok(std::fs::read_to_string(file)?
-        .split('\n')
-        .map(|line| line.trim().to_owned())
-        .collect::<Vec<String>>())
+/// Read the lines of a file, or `None` if it is a binary file.
+fn get_lines_from_file(file: &PathBuf, encoding: Encoding) -> std::io::Result<Option<Vec<String>>> {
+    let Some(contents) = decode(std::fs::read(file)?, encoding)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(
+        split_lines(&contents)
+            .map(str::to_owned)
+            .collect::<Vec<String>>(),
+    ))
+}
+
+impl Line {
+    /// The text that is used for comparisons.
+    pub fn comparable(&self) -> &str {
+        self.normalized.as_deref().unwrap_or(&self.text)
+    }
+
+    /// Whether the line is left out of comparisons by `--strip`.
+    ///
+    /// Lines that are left with no text once their comments are stripped only had a comment.
+    pub fn is_stripped(&self, strip: &[Strip]) -> bool {
+        if self.raw.trim().is_empty() {
+            strip.contains(&Strip::BlankLines)
+        } else {
+            self.text.is_empty()
+        }
+    }
 }
 
 impl CompFile {
-    pub fn current_line(&self) -> &String {
+    pub fn new(file: PathBuf, lines: Vec<Line>) -> Self {
+        let lines = Arc::new(lines);
+        Self::with_source(file, lines.clone(), lines)
+    }
+
+    /// Like `new`, but leaves the lines that are stripped (see `--strip`) out of comparisons.
+    pub fn stripped(file: PathBuf, source: Vec<Line>, strip: &[Strip]) -> Self {
+        if strip.is_empty() {
+            return Self::new(file, source);
+        }
+
+        let lines = source
+            .iter()
+            .filter(|line| !line.is_stripped(strip))
+            .cloned()
+            .collect();
+        Self::with_source(file, Arc::new(lines), Arc::new(source))
+    }
+
+    fn with_source(file: PathBuf, lines: Arc<Vec<Line>>, source: Arc<Vec<Line>>) -> Self {
+        let mut index = LineIndex::new();
+        for (i, line) in lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| !line.suppressed)
+        {
+            index.entry(line.id).or_default().push(i);
+        }
+
+        Self {
+            file,
+            lines,
+            source,
+            index: Arc::new(index),
+            start: 0,
+        }
+    }
+
+    /// The line number and size in the file of the `size` compared lines from `start`.
+    ///
+    /// Stripped lines in between are counted, so the block covers every line of the file from
+    /// its first line to its last.
+    pub fn span(&self, start: usize, size: usize) -> (usize, usize) {
+        let first = self.lines[start].number;
+        let last = self.lines[start + size - 1].number;
+
+        (first, last - first + 1)
+    }
+
+    pub fn current_line(&self) -> &Line {
         &self.lines[self.start]
     }
 
-    pub fn from_files(f1: &PathBuf, f2: &PathBuf) -> Option<(Self, Self)> {
-        match (get_lines_from_file(f1), get_lines_from_file(f2)) {
-            (Ok(lines1), Ok(lines2)) => Some((
-                Self {
-                    file: f1.clone(),
-                    lines: lines1,
-                    start: 0,
-                },
-                Self {
-                    file: f2.clone(),
-                    lines: lines2,
-                    start: 0,
-                },
-            )),
-            _ => None,
+    /// The number of lines, not counting the empty "line" after a final line ending.
+    pub fn line_count(&self) -> usize {
+        match self.source.last() {
+            Some(line) if line.raw.is_empty() => self.source.len() - 1,
+            _ => self.source.len(),
+        }
+    }
+
+    /// The lines of a block in this file, cut short if it goes past the end.
+    pub fn block(&self, info: &JsonBlockInfo) -> &[Line] {
+        let start = std::cmp::min(info.starting_line - 1, self.source.len());
+        let end = std::cmp::min(start + info.block_length, self.source.len());
+
+        &self.source[start..end]
+    }
+
+    /// The lines of a block in this file that were compared.
+    pub fn compared_block(&self, info: &JsonBlockInfo) -> &[Line] {
+        let end_line = info.starting_line + info.block_length;
+        let start = self
+            .lines
+            .partition_point(|line| line.number < info.starting_line);
+        let end = self.lines.partition_point(|line| line.number < end_line);
+
+        &self.lines[start..end]
+    }
+
+    /// For every line of a block in this file, its index in `compared_block`, or `None` if it
+    /// wasn't compared.
+    pub fn compared_indices(&self, info: &JsonBlockInfo) -> Vec<Option<usize>> {
+        let compared = self.compared_block(info);
+        let mut k = 0;
+
+        self.block(info)
+            .iter()
+            .map(|line| {
+                let is_compared = compared.get(k).is_some_and(|c| c.number == line.number);
+                k += usize::from(is_compared);
+                is_compared.then(|| k - 1)
+            })
+            .collect()
+    }
+}
+
+impl FileCache {
+    /// Read every file and intern its lines.
+    ///
+    /// Lines are interned after normalization, so lines that only differ in the normalized tokens
+    /// get the same id. Binary files are skipped, and files that can't be read are skipped and
+    /// recorded in `errors`.
+    pub fn from_args(args: &Cli) -> Self {
+        let mut interner: HashMap<String, usize> = HashMap::new();
+        let mut cache = Self::default();
+
+        for file in &args.files {
+            let lines = match get_lines_from_file(file, args.encoding) {
+                Ok(Some(lines)) => lines,
+                Ok(None) => {
+                    if args.verbose {
+                        eprintln!("Skipping binary file {}", file.display());
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    cache.errors.push(JsonFileError {
+                        path: file.clone(),
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            let code = Language::from_path(file)
+                .filter(|_| args.strip.contains(&Strip::Comments))
+                .map(|language| strip_comments(&lines, language));
+            let suppressed = suppressed_lines(&lines);
+            let lines = lines
+                .into_iter()
+                .zip(suppressed)
+                .enumerate()
+                .map(|(i, (raw, suppressed))| {
+                    let text = match &code {
+                        Some(code) => code[i].trim().to_owned(),
+                        None => raw.trim().to_owned(),
+                    };
+                    let normalized = if args.normalize.is_empty() {
+                        None
+                    } else {
+                        Some(normalize(&text, &args.normalize))
+                    };
+                    let key = normalized.as_ref().unwrap_or(&text);
+
+                    let next_id = interner.len();
+                    let id = *interner.entry(key.clone()).or_insert(next_id);
+                    Line {
+                        id,
+                        text,
+                        raw,
+                        normalized,
+                        suppressed,
+                        number: i + 1,
+                    }
+                })
+                .collect();
+
+            cache
+                .files
+                .push(CompFile::stripped(file.clone(), lines, &args.strip));
+        }
+
+        cache.unique_lines = interner.len();
+        cache
+    }
+
+    /// The number of lines in every file that are suppressed by `superdiff:ignore` comments.
+    pub fn suppressed_lines(&self) -> usize {
+        self.files
+            .iter()
+            .map(|f| f.source.iter().filter(|line| line.suppressed).count())
+            .sum()
+    }
+
+    /// Look up files by their path.
+    pub fn by_path(&self) -> HashMap<&Path, &CompFile> {
+        self.files.iter().map(|f| (f.file.as_path(), f)).collect()
+    }
+
+    /// Fill in the metrics of every file and directory, and of the whole run.
+    ///
+    /// Every file that was read is added to `files`, even if it isn't part of any match.
+    pub fn add_metrics(&self, root: &mut JsonRoot) {
+        let duplicated_lines: HashMap<PathBuf, HashSet<usize>> = root
+            .duplicated_lines()
+            .into_iter()
+            .map(|(path, lines)| (path.to_owned(), lines))
+            .collect();
+
+        for f in &self.files {
+            let total_lines = f.line_count();
+            let metrics = JsonMetrics::new(
+                total_lines,
+                duplicated_lines.get(&f.file).map_or(0, |lines| {
+                    lines.iter().filter(|&&line| line <= total_lines).count()
+                }),
+            );
+
+            root.files
+                .entry(f.file.clone())
+                .or_insert(JsonFileInfo {
+                    count_blocks: 0,
+                    metrics: None,
+                })
+                .metrics = Some(metrics);
+
+            for directory in f.file.ancestors().skip(1) {
+                if directory.as_os_str().is_empty() {
+                    continue;
+                }
+
+                let entry = root.directories.entry(directory.to_owned()).or_default();
+                *entry = entry.add(&metrics);
+            }
+
+            root.summary = root.summary.add(&metrics);
+        }
+    }
+
+    /// Fill in the fingerprint of every match.
+    ///
+    /// Each block is hashed by the text that was compared, so the fingerprint doesn't depend on
+    /// paths, line numbers, or which block ended up as the key of the group. The group gets the
+    /// smallest hash of its blocks.
+    pub fn add_fingerprints(&self, root: &mut JsonRoot) {
+        let files = self.by_path();
+
+        for jm in &mut root.matches {
+            let fingerprint = jm
+                .blocks
+                .iter()
+                .flat_map(|(path, infos)| infos.iter().map(move |info| (path, info)))
+                .filter_map(|(path, info)| {
+                    let lines = files.get(path.as_path())?.compared_block(info);
+
+                    Some(lines.iter().fold(FNV_OFFSET_BASIS, |hash, line| {
+                        fnv1a(fnv1a(hash, line.comparable().as_bytes()), b"\n")
+                    }))
+                })
+                .min();
+
+            if let Some(fingerprint) = fingerprint {
+                jm.fingerprint = format!("{fingerprint:016x}");
+            }
         }
     }
 }
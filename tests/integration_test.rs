@@ -1,8 +1,21 @@
-use superdiff::cli::{Cli, ReportingMode};
-use superdiff::types::JsonRoot;
+use superdiff::baseline::Baseline;
+use superdiff::cli::{Cli, ColorChoice, Engine, ReportingMode, SortOrder};
+use superdiff::config::Origin;
+use superdiff::cpd;
+use superdiff::encoding::Encoding;
+use superdiff::gate::{self, GateFailure};
+use superdiff::gitlab;
+use superdiff::html;
+use superdiff::lang::Language;
+use superdiff::normalize::Normalization;
+use superdiff::sarif::SarifLog;
+use superdiff::snippet;
+use superdiff::strip::Strip;
+use superdiff::types::{JsonLine, JsonRoot, Match};
 use superdiff::threadpool::ThreadPool;
+use tempfile::TempDir;
 
-use std::fs::read_to_string;
+use std::fs::{create_dir_all, read_to_string, write};
 use std::path::PathBuf;
 
 macro_rules! vec_pathbuf {
@@ -53,6 +66,7 @@ fn it_outputs_correct_matches_for_terraria_clone() {
         files: terraria_clone_files(),
         reporting_mode: ReportingMode::Json,
         worker_threads: 4,
+        ..Cli::default()
     };
 
     let mut pool = ThreadPool::from(args);
@@ -74,6 +88,7 @@ fn it_puts_all_matches_in_same_group() {
         files: similar_matches_files(),
         reporting_mode: ReportingMode::Json,
         worker_threads: 1,
+        ..Cli::default()
     };
 
     let mut pool = ThreadPool::from(args);
@@ -86,6 +101,1067 @@ fn it_puts_all_matches_in_same_group() {
     assert_eq!(matches, expected);
 }
 
+#[test]
+fn it_finds_the_same_matches_with_suffix_array() {
+    // Lines with the same id after normalization, but only long enough in a.py
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    write(
+        dir.join("a.py"),
+        "longname = 1\nlongname = 2\nlongname = 3\nprint(longname)\n",
+    )
+    .unwrap();
+    write(dir.join("b.py"), "x = 1\nx = 2\nx = 3\nprint(x)\n").unwrap();
+
+    let inputs = [
+        vec_pathbuf!["examples/really-bad-code.py"],
+        similar_matches_files(),
+        vec_pathbuf![
+            "examples/renamed-identifiers/original.py",
+            "examples/renamed-identifiers/renamed.py"
+        ],
+        vec_pathbuf![
+            "examples/really-bad-code.py",
+            "examples/similar-matches-in-same-group/file1.txt",
+            "examples/similar-matches-in-same-group/file2.txt",
+            "examples/renamed-identifiers/original.py",
+            "examples/renamed-identifiers/renamed.py"
+        ],
+        vec![dir.join("a.py"), dir.join("b.py")],
+    ];
+
+    for files in inputs {
+        for (block_threshold, line_threshold) in (3..=5).flat_map(|b| [(b, 1), (b, 8)]) {
+            let args = Cli {
+                lev_threshold: 0,
+                line_threshold,
+                block_threshold,
+                verbose: false,
+                files: files.clone(),
+                normalize: vec![Normalization::Identifiers, Normalization::Literals],
+                reporting_mode: ReportingMode::Json,
+                worker_threads: 1,
+                ..Cli::default()
+            };
+            let suffix_args = Cli {
+                engine: Engine::SuffixArray,
+                worker_threads: 2,
+                ..args.clone()
+            };
+
+            let pairwise_matches = ThreadPool::from(args).run_and_get_results();
+            let suffix_matches = ThreadPool::from(suffix_args).run_and_get_results();
+
+            assert!(pairwise_matches.unique_matches() > 0);
+            assert_eq!(
+                pairwise_matches, suffix_matches,
+                "{files:?} -b {block_threshold} -l {line_threshold}"
+            );
+        }
+    }
+}
+
+#[test]
+fn it_reports_sarif_with_stable_fingerprints() {
+    let args = Cli {
+        block_threshold: 4,
+        files: vec_pathbuf!["examples/really-bad-code.py"],
+        reporting_mode: ReportingMode::Sarif,
+        ..Cli::default()
+    };
+    let suffix_args = Cli {
+        engine: Engine::SuffixArray,
+        ..args.clone()
+    };
+
+    let matches = ThreadPool::from(args).run_and_get_results();
+    let suffix_matches = ThreadPool::from(suffix_args).run_and_get_results();
+    let sarif: serde_json::Value = serde_json::from_str(&SarifLog::from(&matches).json()).unwrap();
+    let results = sarif["runs"][0]["results"].as_array().unwrap();
+    let block_count: usize = matches.matches[0].blocks.values().map(Vec::len).sum();
+
+    assert_eq!(sarif["version"], "2.1.0");
+    assert_eq!(results.len(), block_count);
+    for result in results {
+        assert_eq!(
+            result["relatedLocations"].as_array().unwrap().len(),
+            block_count - 1
+        );
+    }
+    assert_eq!(matches.matches[0].fingerprint.len(), 16);
+    assert_eq!(
+        matches.matches[0].fingerprint,
+        suffix_matches.matches[0].fingerprint
+    );
+    assert_eq!(
+        SarifLog::from(&matches).json(),
+        SarifLog::from(&suffix_matches).json()
+    );
+}
+
+#[test]
+fn it_reports_cpd_xml_with_code_fragments() {
+    let args = Cli {
+        block_threshold: 4,
+        files: vec_pathbuf!["examples/really-bad-code.py"],
+        reporting_mode: ReportingMode::CpdXml,
+        ..Cli::default()
+    };
+
+    let mut pool = ThreadPool::from(args);
+    let matches = pool.run_and_get_results();
+    let xml = cpd::report(&matches, &pool.cache());
+
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<pmd-cpd>"));
+    assert_eq!(xml.matches("<duplication lines=\"5\"").count(), 1);
+    assert!(xml.contains("<file line=\"5\" endline=\"9\" path=\"examples/really-bad-code.py\"/>"));
+    assert!(xml.contains("<file line=\"11\" endline=\"15\" path=\"examples/really-bad-code.py\"/>"));
+    assert!(xml.contains("<codefragment><![CDATA[        self.alpha = 12\n"));
+}
+
+#[test]
+fn it_reports_gitlab_code_quality_issues() {
+    let args = Cli {
+        block_threshold: 4,
+        files: vec_pathbuf!["examples/really-bad-code.py"],
+        reporting_mode: ReportingMode::Gitlab,
+        ..Cli::default()
+    };
+
+    let matches = ThreadPool::from(args).run_and_get_results();
+    let issues: serde_json::Value = serde_json::from_str(&gitlab::json(&matches)).unwrap();
+    let issues = issues.as_array().unwrap();
+
+    assert_eq!(issues.len(), 2);
+    assert_eq!(issues[0]["location"]["path"], "examples/really-bad-code.py");
+    assert_eq!(issues[0]["location"]["lines"]["begin"], 5);
+    assert_eq!(issues[1]["location"]["lines"]["begin"], 11);
+    assert_eq!(issues[0]["severity"], "info");
+    assert_ne!(issues[0]["fingerprint"], issues[1]["fingerprint"]);
+}
+
+#[test]
+fn it_reports_html_with_highlighted_differences() {
+    let args = Cli {
+        lev_threshold: 5,
+        block_threshold: 4,
+        files: vec_pathbuf!["examples/really-bad-code.py"],
+        reporting_mode: ReportingMode::Html,
+        ..Cli::default()
+    };
+
+    let mut pool = ThreadPool::from(args.clone());
+    let matches = pool.run_and_get_results();
+    let page = html::report(&args, &matches, &pool.cache());
+
+    assert!(page.starts_with("<!DOCTYPE html>"));
+    assert!(page.trim_end().ends_with("</html>"));
+    assert_eq!(
+        page.matches("<h2 id=\"match-").count(),
+        matches.unique_matches()
+    );
+    assert!(page.contains("<td>examples/really-bad-code.py</td>"));
+    assert!(page.contains("<mark>"));
+}
+
+#[test]
+fn it_prints_snippets_with_context_and_inline_diffs() {
+    let args = Cli {
+        lev_threshold: 5,
+        block_threshold: 4,
+        files: vec_pathbuf!["examples/really-bad-code.py"],
+        snippets: true,
+        context: 1,
+        color: ColorChoice::Never,
+        ..Cli::default()
+    };
+
+    let mut pool = ThreadPool::from(args.clone());
+    let matches = pool.run_and_get_results();
+    let text = snippet::report(&args, &matches, &pool.cache());
+
+    assert_eq!(
+        text.matches("=== MATCH ===").count(),
+        matches.unique_matches()
+    );
+    assert!(text.contains(" 4 |     def __init__(self):\n 5 >         self.alpha = 12\n"));
+    assert!(text.contains("26 >     def do_something{+_else+}(self):"));
+    assert!(!text.contains('\x1b'));
+}
+
+#[test]
+fn it_formats_json_lines() {
+    let args = Cli {
+        block_threshold: 4,
+        files: vec_pathbuf!["examples/really-bad-code.py"],
+        reporting_mode: ReportingMode::Jsonl,
+        ..Cli::default()
+    };
+    let pair = (
+        Match {
+            file: PathBuf::from("a.py"),
+            line: 5,
+            size: 4,
+            gap_lines: 0,
+        },
+        Match {
+            file: PathBuf::from("b.py"),
+            line: 11,
+            size: 5,
+            gap_lines: 1,
+        },
+    );
+
+    assert_eq!(
+        JsonLine::from_pair(&pair).json(),
+        r#"{"type":"pair","blocks":[{"file":"a.py","starting_line":5,"block_length":4},{"file":"b.py","starting_line":11,"block_length":5,"gap_lines":1}]}"#
+    );
+
+    let matches = ThreadPool::from(args).run_and_get_results();
+    let line = JsonLine::Result(&matches).json();
+    let mut value: serde_json::Value = serde_json::from_str(&line).unwrap();
+
+    assert!(!line.contains('\n'));
+    assert_eq!(value["type"], "result");
+    value.as_object_mut().unwrap().remove("type");
+    assert_eq!(serde_json::from_value::<JsonRoot>(value).unwrap(), matches);
+}
+
+#[test]
+fn it_sorts_matches_the_same_way_every_time() {
+    let args = Cli {
+        lev_threshold: 5,
+        block_threshold: 4,
+        files: vec_pathbuf!["examples/really-bad-code.py"],
+        reporting_mode: ReportingMode::Json,
+        ..Cli::default()
+    };
+    let size_args = Cli {
+        sort: SortOrder::Size,
+        ..args.clone()
+    };
+
+    let first_lines = |matches: &JsonRoot| -> Vec<usize> {
+        matches
+            .matches
+            .iter()
+            .map(|jm| jm.blocks.values().next().unwrap()[0].starting_line)
+            .collect()
+    };
+    let matches = ThreadPool::from(args.clone()).run_and_get_results();
+    let size_matches = ThreadPool::from(size_args).run_and_get_results();
+
+    assert_eq!(first_lines(&matches), vec![5, 16]);
+    assert_eq!(first_lines(&size_matches), vec![16, 5]);
+    for _ in 0..5 {
+        assert_eq!(
+            ThreadPool::from(args.clone()).run_and_get_results().json(),
+            matches.json()
+        );
+    }
+
+    // Both matches start on the first line, so the blocks after it decide
+    let tied_args = Cli {
+        lev_threshold: 0,
+        block_threshold: 2,
+        files: vec_pathbuf!["examples/similar-matches-in-same-group/file1.txt"],
+        ..args
+    };
+    let sizes: Vec<usize> = ThreadPool::from(tied_args)
+        .run_and_get_results()
+        .matches
+        .iter()
+        .map(|jm| jm.blocks.values().next().unwrap()[0].block_length)
+        .collect();
+    assert_eq!(sizes, vec![2, 4]);
+}
+
+#[test]
+fn it_computes_duplication_metrics() {
+    let args = Cli {
+        block_threshold: 4,
+        files: vec_pathbuf![
+            "examples/really-bad-code.py",
+            "examples/renamed-identifiers/original.py"
+        ],
+        summary: true,
+        ..Cli::default()
+    };
+
+    let matches = ThreadPool::from(args).run_and_get_results();
+    let file_metrics = |path: &str| {
+        matches.files[&PathBuf::from(path)]
+            .metrics
+            .expect("every file that was read has metrics")
+    };
+
+    assert_eq!(file_metrics("examples/really-bad-code.py").total_lines, 37);
+    assert_eq!(
+        file_metrics("examples/really-bad-code.py").duplicated_lines,
+        10
+    );
+    assert_eq!(
+        file_metrics("examples/renamed-identifiers/original.py").duplicated_lines,
+        0
+    );
+    assert_eq!(
+        matches.directories[&PathBuf::from("examples")].total_lines,
+        44
+    );
+    assert_eq!(matches.summary.duplicated_lines, 10);
+    assert!((matches.summary.duplication_ratio - 10.0 / 44.0).abs() < 1e-9);
+    assert!(matches.matches[0]
+        .files
+        .values()
+        .all(|info| info.metrics.is_none()));
+    assert_eq!(
+        matches
+            .summary_table()
+            .lines()
+            .last()
+            .unwrap()
+            .split_whitespace()
+            .collect::<Vec<&str>>(),
+        vec!["Total", "44", "10", "22.7%"]
+    );
+
+    let deserialized: JsonRoot = serde_json::from_str(&matches.json()).unwrap();
+    assert_eq!(deserialized.files.len(), matches.files.len());
+    assert_eq!(deserialized.summary.total_lines, 44);
+    assert_eq!(deserialized.summary.duplicated_lines, 10);
+}
+
+#[test]
+fn it_fails_quality_gates_above_thresholds() {
+    let args = Cli {
+        block_threshold: 4,
+        files: vec_pathbuf!["examples/really-bad-code.py"],
+        ..Cli::default()
+    };
+    let strict_args = Cli {
+        fail_on_duplication_ratio: Some(0.25),
+        fail_on_groups: Some(0),
+        fail_on_block_size: Some(4),
+        ..args.clone()
+    };
+    let lenient_args = Cli {
+        fail_on_duplication_ratio: Some(0.5),
+        fail_on_groups: Some(1),
+        fail_on_block_size: Some(5),
+        ..args.clone()
+    };
+
+    let matches = ThreadPool::from(args.clone()).run_and_get_results();
+
+    assert!(gate::check(&args, &matches).is_empty());
+    assert!(gate::check(&lenient_args, &matches).is_empty());
+    assert_eq!(
+        gate::check(&strict_args, &matches),
+        vec![
+            GateFailure::DuplicationRatio {
+                ratio: 10.0 / 37.0,
+                max: 0.25
+            },
+            GateFailure::Groups { groups: 1, max: 0 },
+            GateFailure::BlockSize { size: 5, max: 4 },
+        ]
+    );
+}
+
+#[test]
+fn it_leaves_out_matches_in_the_baseline() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    let code = read_to_string("examples/really-bad-code.py").unwrap();
+    write(dir.join("code.py"), &code).unwrap();
+
+    let args = Cli {
+        lev_threshold: 5,
+        block_threshold: 4,
+        files: vec![dir.join("code.py")],
+        write_baseline: Some(dir.join("baseline.json")),
+        ..Cli::default()
+    };
+
+    let mut pool = ThreadPool::from(args.clone());
+    let matches = pool.run_and_get_results();
+    pool.found().write(&dir.join("baseline.json")).unwrap();
+    let baseline = Baseline::read(&dir.join("baseline.json")).unwrap();
+
+    assert_eq!(baseline.fingerprints.len(), matches.unique_matches());
+
+    // Shift every line down, and add a new duplicate at the end
+    let new_duplicate = "def brand_new_function():\n    xs = range(10)\n    ys = [x * 3 for x in xs]\n    zs = [y + 1 for y in ys]\n    return zs\n";
+    write(
+        dir.join("code.py"),
+        format!("import os\nimport sys\n\n{code}\n{new_duplicate}\n{new_duplicate}"),
+    )
+    .unwrap();
+
+    let matches_with_baseline = ThreadPool::from(args)
+        .with_baseline(baseline)
+        .run_and_get_results();
+
+    assert_eq!(
+        matches_with_baseline.known_matches,
+        matches.unique_matches()
+    );
+    assert_eq!(matches_with_baseline.unique_matches(), 1);
+    assert_eq!(
+        matches_with_baseline.matches[0].blocks[&dir.join("code.py")][0].starting_line,
+        42
+    );
+}
+
+#[test]
+fn it_finds_renamed_identifiers_when_normalizing() {
+    let args = Cli {
+        lev_threshold: 0,
+        line_threshold: 1,
+        block_threshold: 5,
+        verbose: false,
+        files: vec_pathbuf![
+            "examples/renamed-identifiers/original.py",
+            "examples/renamed-identifiers/renamed.py"
+        ],
+        reporting_mode: ReportingMode::Json,
+        worker_threads: 1,
+        ..Cli::default()
+    };
+    let normalized_args = Cli {
+        normalize: vec![Normalization::Identifiers, Normalization::Literals],
+        ..args.clone()
+    };
+
+    let matches = ThreadPool::from(args).run_and_get_results();
+    let normalized_matches = ThreadPool::from(normalized_args).run_and_get_results();
+
+    assert_eq!(matches.unique_matches(), 0);
+    assert_eq!(normalized_matches.unique_matches(), 1);
+    for blocks in normalized_matches.matches[0].blocks.values() {
+        assert_eq!(blocks[0].starting_line, 1);
+        // Includes the empty line after the final newline
+        assert_eq!(blocks[0].block_length, 8);
+    }
+}
+
+#[test]
+fn it_walks_directories() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    create_dir_all(dir.join("src")).unwrap();
+    create_dir_all(dir.join(".hidden")).unwrap();
+    write(dir.join(".gitignore"), "target/\n").unwrap();
+    write(dir.join(".ignore"), "*.bak\n").unwrap();
+    create_dir_all(dir.join("target")).unwrap();
+    for file in [
+        "src/a.rs",
+        "src/b.rs",
+        "target/c.rs",
+        "d.bak",
+        ".hidden/e.rs",
+    ] {
+        write(dir.join(file), "fn main() {}\n").unwrap();
+    }
+
+    let expand = |args: Cli| {
+        let mut args = Cli {
+            files: vec![dir.to_path_buf()],
+            ..args
+        };
+        args.expand_directories();
+        let mut files: Vec<PathBuf> = args
+            .files
+            .iter()
+            .map(|f| f.strip_prefix(dir).unwrap().to_owned())
+            .collect();
+        files.sort();
+        files
+    };
+
+    assert_eq!(expand(Cli::default()), vec_pathbuf!["src/a.rs", "src/b.rs"]);
+    assert_eq!(
+        expand(Cli {
+            no_ignore_vcs: true,
+            ..Cli::default()
+        }),
+        vec_pathbuf!["src/a.rs", "src/b.rs", "target/c.rs"]
+    );
+    assert_eq!(
+        expand(Cli {
+            no_ignore_dot: true,
+            ..Cli::default()
+        }),
+        vec_pathbuf!["d.bak", "src/a.rs", "src/b.rs"]
+    );
+    assert_eq!(
+        expand(Cli {
+            hidden: true,
+            ..Cli::default()
+        }),
+        vec_pathbuf![
+            ".gitignore",
+            ".hidden/e.rs",
+            ".ignore",
+            "src/a.rs",
+            "src/b.rs"
+        ]
+    );
+}
+
+#[test]
+fn it_filters_files() {
+    let filter = |args: Cli| {
+        let mut args = Cli {
+            files: vec_pathbuf!["./examples", "README.md", "scripts/populate-data.sh"],
+            ..args
+        };
+        args.expand_directories();
+        args.filter_files();
+        args.files
+    };
+
+    assert_eq!(
+        filter(Cli {
+            include: vec!["*.py".to_owned()],
+            exclude: vec!["examples/renamed-identifiers/**".to_owned()],
+            ..Cli::default()
+        }),
+        vec_pathbuf!["./examples/really-bad-code.py"]
+    );
+    assert_eq!(
+        filter(Cli {
+            lang: vec![Language::Python, Language::Shell],
+            exclude: vec!["renamed.py".to_owned()],
+            ..Cli::default()
+        }),
+        vec_pathbuf![
+            "./examples/really-bad-code.py",
+            "./examples/renamed-identifiers/original.py",
+            "scripts/populate-data.sh"
+        ]
+    );
+}
+
+#[test]
+fn it_matches_globs_below_the_searched_directory() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    for file in ["a.py", "target/b.py", "src/target/c.py", "src/d.py"] {
+        create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+        write(dir.join(file), "x = 1\n").unwrap();
+    }
+    let filter = |mut args: Cli| {
+        args.expand_directories();
+        args.filter_files();
+        args.files
+    };
+
+    // Every `target` directory, even though the path is absolute
+    assert_eq!(
+        filter(Cli {
+            files: vec![dir.to_owned()],
+            exclude: vec!["target/**".to_owned()],
+            ..Cli::default()
+        }),
+        vec![dir.join("a.py"), dir.join("src/d.py")]
+    );
+
+    // Relative to the configuration file
+    assert_eq!(
+        filter(Cli {
+            files: vec![dir.join("src")],
+            exclude: vec!["src/target/**".to_owned()],
+            config: Some(dir.join(".superdiff.toml")),
+            ..Cli::default()
+        }),
+        vec![dir.join("src/d.py")]
+    );
+}
+
+#[test]
+fn it_reports_unreadable_files() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    write(dir.join("binary.bin"), b"\x7fELF\x00\x01\x02").unwrap();
+    write(dir.join("latin1.txt"), b"caf\xe9\n").unwrap();
+
+    let args = Cli {
+        block_threshold: 4,
+        files: vec![
+            PathBuf::from("examples/really-bad-code.py"),
+            dir.join("binary.bin"),
+            dir.join("latin1.txt"),
+            dir.join("missing.txt"),
+        ],
+        encoding: Encoding::Utf8,
+        reporting_mode: ReportingMode::Json,
+        ..Cli::default()
+    };
+
+    let matches = ThreadPool::from(args).run_and_get_results();
+    let error_paths: Vec<PathBuf> = matches.errors.iter().map(|e| e.path.clone()).collect();
+
+    assert_eq!(matches.unique_matches(), 1);
+    assert_eq!(
+        error_paths,
+        vec![dir.join("latin1.txt"), dir.join("missing.txt")]
+    );
+    assert!(matches.json().contains("\"errors\":[{\"path\":"));
+}
+
+#[test]
+fn it_decodes_other_encodings_and_line_endings() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+
+    let text = "first line of the block\nsecond line here\nthird line of it\n";
+    let utf16: Vec<u8> = [0xff, 0xfe]
+        .into_iter()
+        .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+        .collect();
+    write(dir.join("unix.txt"), text).unwrap();
+    write(dir.join("windows.txt"), text.replace('\n', "\r\n")).unwrap();
+    write(dir.join("utf16.txt"), utf16).unwrap();
+
+    let args = Cli {
+        block_threshold: 3,
+        files: vec![
+            dir.join("unix.txt"),
+            dir.join("windows.txt"),
+            dir.join("utf16.txt"),
+        ],
+        reporting_mode: ReportingMode::Json,
+        ..Cli::default()
+    };
+
+    let matches = ThreadPool::from(args).run_and_get_results();
+
+    assert!(matches.errors.is_empty());
+    assert_eq!(matches.unique_matches(), 1);
+    assert_eq!(matches.matches[0].blocks.len(), 3);
+}
+
+#[test]
+fn it_never_matches_suppressed_lines() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    let table = "RED = 1\nGREEN = 2\nBLUE = 3\nCYAN = 4\nMAGENTA = 5\nYELLOW = 6\n";
+    write(
+        dir.join("colors.py"),
+        format!("{table}\n# superdiff:ignore-start\n{table}# superdiff:ignore-end\n\n# superdiff:ignore-next 6\n{table}"),
+    )
+    .unwrap();
+
+    for engine in [Engine::Pairwise, Engine::SuffixArray] {
+        for lev_threshold in [0, 2] {
+            let args = Cli {
+                lev_threshold,
+                block_threshold: 3,
+                engine,
+                files: vec![dir.join("colors.py")],
+                ..Cli::default()
+            };
+
+            let matches = ThreadPool::from(args).run_and_get_results();
+
+            assert_eq!(matches.unique_matches(), 0);
+            assert_eq!(matches.suppressed_lines, 15);
+        }
+    }
+
+    // Gaps can't skip over suppressed lines either
+    write(dir.join("p.py"), "a = 1\nb = 2\nc = 3\nd = 4\ne = 5\n").unwrap();
+    write(
+        dir.join("q.py"),
+        "a = 1\nb = 2\nc = 3  # superdiff:ignore-next 0\nd = 4\ne = 5\n",
+    )
+    .unwrap();
+    let args = Cli {
+        block_threshold: 4,
+        max_gap: 1,
+        min_similarity: 0.5,
+        files: vec![dir.join("p.py"), dir.join("q.py")],
+        ..Cli::default()
+    };
+
+    let matches = ThreadPool::from(args).run_and_get_results();
+
+    assert_eq!(matches.unique_matches(), 0);
+    assert_eq!(matches.suppressed_lines, 1);
+
+    // Without the markers, all three tables match
+    write(dir.join("colors.py"), format!("{table}\n{table}\n{table}")).unwrap();
+    let args = Cli {
+        block_threshold: 3,
+        files: vec![dir.join("colors.py")],
+        ..Cli::default()
+    };
+
+    let matches = ThreadPool::from(args).run_and_get_results();
+
+    assert_eq!(matches.unique_matches(), 1);
+    assert_eq!(matches.matches[0].blocks[&dir.join("colors.py")].len(), 3);
+    assert_eq!(matches.suppressed_lines, 0);
+}
+
+#[test]
+fn it_strips_comments_and_blank_lines_but_reports_lines_of_the_file() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    write(
+        dir.join("a.c"),
+        "/* License: MIT\n * Copyright A */\nint add(int a, int b) {\n    // add them\n    int c = a + b;\n    return c;\n}\n",
+    )
+    .unwrap();
+    write(
+        dir.join("b.c"),
+        "int add(int a, int b) {\n\n    int c = a + b; /* sum */\n    return c;\n}\n",
+    )
+    .unwrap();
+
+    let args = Cli {
+        block_threshold: 4,
+        files: vec![dir.join("a.c"), dir.join("b.c")],
+        ..Cli::default()
+    };
+
+    let matches = ThreadPool::from(args.clone()).run_and_get_results();
+
+    assert_eq!(matches.unique_matches(), 0);
+
+    for engine in [Engine::Pairwise, Engine::SuffixArray] {
+        let args = Cli {
+            strip: vec![Strip::Comments, Strip::BlankLines],
+            engine,
+            ..args.clone()
+        };
+
+        let matches = ThreadPool::from(args).run_and_get_results();
+
+        assert_eq!(matches.unique_matches(), 1);
+        let block = |file| {
+            let info = &matches.matches[0].blocks[&dir.join(file)][0];
+            (info.starting_line, info.block_length)
+        };
+        assert_eq!(block("a.c"), (3, 5));
+        assert_eq!(block("b.c"), (1, 5));
+    }
+}
+
+#[test]
+fn it_shows_blocks_as_they_are_in_the_file_when_stripping() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    write(
+        dir.join("c.c"),
+        "int add(int a, int b) {\n    // add them up\n    int c = a + b; /* sum */\n    return c;\n}\n",
+    )
+    .unwrap();
+    write(
+        dir.join("d.c"),
+        "int add(int x, int y) {\n    int c = x + y;\n    return c;\n}\n",
+    )
+    .unwrap();
+
+    let args = Cli {
+        block_threshold: 4,
+        files: vec![dir.join("c.c"), dir.join("d.c")],
+        strip: vec![Strip::Comments],
+        normalize: vec![Normalization::Identifiers],
+        color: ColorChoice::Never,
+        ..Cli::default()
+    };
+
+    let mut pool = ThreadPool::from(args.clone());
+    let matches = pool.run_and_get_results();
+    let page = html::report(&args, &matches, &pool.cache());
+    let text = snippet::report(&args, &matches, &pool.cache());
+
+    // Comments are shown, and lines are diffed against the line that was compared with them
+    assert_eq!(matches.unique_matches(), 1);
+    assert!(page.contains("<span class=\"line-number\">2</span>    // add them up\n"));
+    assert!(page.contains(
+        "<span class=\"line-number\">3</span>    int c = <mark>a</mark> + <mark>b</mark>; /* sum */\n"
+    ));
+    assert!(page.contains(
+        "<span class=\"line-number\">2</span>    int c = <mark>x</mark> + <mark>y</mark>;\n"
+    ));
+    assert!(text.contains("2 >     // add them up\n"));
+    assert!(text.contains("2 >     int c = [-a-]{+x+} + [-b-]{+y+};\n"));
+}
+
+#[cfg(feature = "syntax")]
+#[test]
+fn it_finds_syntactically_equal_functions() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    write(
+        dir.join("a.py"),
+        "import sys\n\ndef total(xs):\n    result = 0\n    for x in xs:\n        result += x\n    return result\n",
+    )
+    .unwrap();
+    write(
+        dir.join("b.py"),
+        "def total(xs):\n    # Add them up\n    result = 0\n    for x in xs:\n        result   +=   x\n\n    return result\n\ndef other(ys):\n    return [y for y in ys]\n",
+    )
+    .unwrap();
+
+    let args = Cli {
+        block_threshold: 3,
+        engine: Engine::Syntax,
+        files: vec![dir.join("a.py"), dir.join("b.py")],
+        ..Cli::default()
+    };
+
+    let matches = ThreadPool::from(args).run_and_get_results();
+
+    // The bodies of the functions match as well, but are part of the larger match
+    assert_eq!(matches.unique_matches(), 1);
+    let block = |file| {
+        let info = &matches.matches[0].blocks[&dir.join(file)][0];
+        (info.starting_line, info.block_length)
+    };
+    assert_eq!(block("a.py"), (3, 5));
+    assert_eq!(block("b.py"), (1, 7));
+}
+
+#[cfg(not(feature = "syntax"))]
+#[test]
+fn it_rejects_the_syntax_engine_without_the_feature() {
+    let tmp = TempDir::new().unwrap();
+
+    let e = Cli::load_from(["superdiff", "--engine", "syntax"], tmp.path())
+        .err()
+        .unwrap();
+
+    assert!(e.to_string().contains("built without the `syntax` feature"));
+}
+
+#[test]
+fn it_snaps_blocks_to_syntactic_boundaries() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    let body =
+        "}\nint g(int y) {\n    if (y > 0) {\n        y = y * 2;\n        y = y + 1;\n    }\n";
+    write(
+        dir.join("a.c"),
+        format!("int f(int x) {{\n    return x;\n{body}    return y;\n}}\n"),
+    )
+    .unwrap();
+    write(
+        dir.join("b.c"),
+        format!("void h(void) {{\n    return;\n{body}    return -y;\n}}\n"),
+    )
+    .unwrap();
+
+    let args = Cli {
+        block_threshold: 4,
+        files: vec![dir.join("a.c"), dir.join("b.c")],
+        ..Cli::default()
+    };
+    let block = |matches: &JsonRoot, file| {
+        let info = &matches.matches[0].blocks[&dir.join(file)][0];
+        (info.starting_line, info.block_length)
+    };
+
+    let matches = ThreadPool::from(args.clone()).run_and_get_results();
+
+    // Starts on a lone `}`, and ends halfway through `g`
+    assert_eq!(matches.unique_matches(), 1);
+    assert_eq!(block(&matches, "a.c"), (3, 6));
+
+    let snap_args = Cli {
+        snap: true,
+        ..args.clone()
+    };
+    let matches = ThreadPool::from(snap_args).run_and_get_results();
+
+    assert_eq!(matches.unique_matches(), 1);
+    assert_eq!(block(&matches, "a.c"), (5, 4));
+    assert_eq!(block(&matches, "b.c"), (5, 4));
+
+    // Too short once snapped
+    let args = Cli {
+        block_threshold: 5,
+        snap: true,
+        ..args
+    };
+    assert_eq!(
+        ThreadPool::from(args)
+            .run_and_get_results()
+            .unique_matches(),
+        0
+    );
+}
+
+#[test]
+fn it_snaps_every_block_of_a_match_the_same_way() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    let body = "    x = 1;\n    y = 2;\n    if (x) {\n        x = 2;\n    }\n";
+    write(
+        dir.join("a.c"),
+        format!("int f(int x) {{\n    int y = 0;\n{body}    else {{\n        y = 3;\n    }}\n    return y;\n}}\n"),
+    )
+    .unwrap();
+    write(
+        dir.join("b.c"),
+        format!("void g(void) {{\n    int x = 0, y = 0;\n{body}    return;\n}}\n"),
+    )
+    .unwrap();
+
+    let args = Cli {
+        lev_threshold: 0,
+        block_threshold: 2,
+        snap: true,
+        files: vec![dir.join("a.c"), dir.join("b.c")],
+        ..Cli::default()
+    };
+    let matches = ThreadPool::from(args).run_and_get_results();
+    let block = |file| {
+        let info = &matches.matches[0].blocks[&dir.join(file)][0];
+        (info.starting_line, info.block_length)
+    };
+
+    // The `if` can only be kept in b.c, since it goes on with an `else` in a.c
+    assert_eq!(matches.unique_matches(), 1);
+    assert_eq!(block("a.c"), (3, 2));
+    assert_eq!(block("b.c"), (3, 2));
+}
+
+#[test]
+fn it_leaves_matches_with_gap_lines_as_they_are_when_snapping() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    let body = "    if (x) {\n        b = 2;\n        c = 3;\n    }\n    return x;\n}\n";
+    write(
+        dir.join("a.c"),
+        format!("void f(int x) {{\n    a = 1;\n{body}"),
+    )
+    .unwrap();
+    write(
+        dir.join("b.c"),
+        format!("void f(int x) {{\n    a = 1;\n    log();\n{body}"),
+    )
+    .unwrap();
+
+    let args = Cli {
+        block_threshold: 4,
+        max_gap: 1,
+        min_similarity: 0.5,
+        files: vec![dir.join("a.c"), dir.join("b.c")],
+        ..Cli::default()
+    };
+    let snap_args = Cli {
+        snap: true,
+        ..args.clone()
+    };
+
+    let matches = ThreadPool::from(args).run_and_get_results();
+    let snapped = ThreadPool::from(snap_args).run_and_get_results();
+
+    // Trimming both blocks the same way would cut them off at different lines
+    assert_eq!(matches.unique_matches(), 1);
+    assert_eq!(matches.matches[0].blocks[&dir.join("b.c")][0].gap_lines, 1);
+    assert_eq!(snapped, matches);
+}
+
+#[test]
+fn it_merges_matches_that_snap_to_the_same_blocks() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    let f = "int f(int x) {\n    x = x + 1;\n    return x;\n}\n";
+    write(
+        dir.join("a.c"),
+        format!("void g(void) {{\n    y = 0;\n}}\n{f}"),
+    )
+    .unwrap();
+    write(
+        dir.join("b.c"),
+        format!("void h(void) {{\n    y = 0;\n}}\n{f}"),
+    )
+    .unwrap();
+    write(dir.join("c.c"), format!("int k;\n{f}")).unwrap();
+
+    let args = Cli {
+        block_threshold: 4,
+        files: vec![dir.join("a.c"), dir.join("b.c"), dir.join("c.c")],
+        ..Cli::default()
+    };
+    let snap_args = Cli {
+        snap: true,
+        ..args.clone()
+    };
+
+    let matches = ThreadPool::from(args).run_and_get_results();
+    let snapped = ThreadPool::from(snap_args).run_and_get_results();
+
+    // Both matches are trimmed down to `f`
+    assert_eq!(matches.unique_matches(), 2);
+    assert_eq!(snapped.unique_matches(), 1);
+    let blocks = &snapped.matches[0].blocks;
+    assert_eq!(blocks[&dir.join("a.c")][0].starting_line, 4);
+    assert_eq!(blocks[&dir.join("b.c")][0].starting_line, 4);
+    assert_eq!(blocks[&dir.join("c.c")][0].starting_line, 2);
+}
+
+#[test]
+fn it_reads_settings_from_the_closest_configuration_file() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    create_dir_all(dir.join("src/nested")).unwrap();
+    write(
+        dir.join(".superdiff.toml"),
+        "block-threshold = 5\nworker-threads = 2\nexclude = [\"target/**\"]\n\n[profile.ci]\nreporting-mode = \"sarif\"\nworker-threads = 8\nfail-on-groups = 0\n",
+    )
+    .unwrap();
+    let config = dir.join(".superdiff.toml");
+    let file = |profile| Origin::File {
+        path: config.clone(),
+        profile,
+    };
+
+    let args = Cli::load_from(["superdiff", "-b", "7"], &dir.join("src/nested")).unwrap();
+
+    assert_eq!(args.config, Some(config.clone()));
+    assert_eq!(args.block_threshold, 7);
+    assert_eq!(args.origin("block_threshold"), &Origin::CommandLine);
+    assert_eq!(args.worker_threads, 2);
+    assert_eq!(args.origin("worker_threads"), &file(None));
+    assert_eq!(args.exclude, vec!["target/**"]);
+    assert_eq!(args.reporting_mode, ReportingMode::Text);
+    assert_eq!(args.origin("reporting_mode"), &Origin::Default);
+
+    let args = Cli::load_from(["superdiff", "--profile", "ci"], dir).unwrap();
+
+    assert_eq!(args.block_threshold, 5);
+    assert_eq!(args.worker_threads, 8);
+    assert_eq!(args.origin("worker_threads"), &file(Some("ci".to_owned())));
+    assert_eq!(args.reporting_mode, ReportingMode::Sarif);
+    assert_eq!(args.fail_on_groups, Some(0));
+
+    let args = Cli::load_from(["superdiff", "--no-config"], dir).unwrap();
+
+    assert_eq!(args.config, None);
+    assert_eq!(args.block_threshold, 10);
+
+    assert!(Cli::load_from(["superdiff", "--profile", "local"], dir)
+        .err()
+        .unwrap()
+        .to_string()
+        .contains("no profile named `local`"));
+
+    // Settings are checked like the command line
+    write(dir.join("src/.superdiff.toml"), "min-similarity = 1.5\n").unwrap();
+    assert!(Cli::load_from(["superdiff"], &dir.join("src"))
+        .err()
+        .unwrap()
+        .to_string()
+        .contains("min-similarity must be between 0 and 1"));
+    assert!(Cli::load_from(["superdiff", "--min-similarity", "2"], dir).is_err());
+}
+
 #[test]
 fn it_could_probably_check_stdin() {
     let args = Cli {
@@ -96,6 +1172,7 @@ fn it_could_probably_check_stdin() {
         files: vec![],
         reporting_mode: ReportingMode::Json,
         worker_threads: 1,
+        ..Cli::default()
     };
 
     assert!(args.files_from_stdin());